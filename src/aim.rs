@@ -0,0 +1,227 @@
+use {
+    super::game_state::GameState,
+    bevy::{prelude::*, render::camera::RenderTarget, window::PrimaryWindow},
+};
+
+pub struct AimPlugin;
+
+impl Plugin for AimPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldCursor>().add_systems(
+            Update,
+            (
+                track_cursor,
+                (switch_aim_sources, aim_with_cursor, aim_with_gamepad)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct WorldCursor {
+    pub world_pos: Option<Vec2>,
+}
+
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct AimTarget(pub Vec2);
+
+#[derive(Component, Default, Clone, Copy)]
+pub enum AimSource {
+    #[default]
+    Cursor,
+    Gamepad(Gamepad),
+}
+
+#[derive(Component, Default)]
+pub struct AimDevices {
+    pub cursor: bool,
+    pub gamepad: Option<Gamepad>,
+}
+
+const GAMEPAD_AIM_DISTANCE: f32 = 32.;
+const GAMEPAD_AIM_DEADZONE: f32 = 0.25;
+
+fn track_cursor(
+    mut cursor: ResMut<WorldCursor>,
+    win_qry: Query<(Entity, &Window)>,
+    primary_win_qry: Query<Entity, With<PrimaryWindow>>,
+    cam_qry: Query<(&Camera, &GlobalTransform)>,
+) {
+    cursor.world_pos = None;
+
+    let primary_win_id = primary_win_qry.get_single().ok();
+    for (win_id, win) in win_qry.iter() {
+        let Some(cursor_pos) = win.cursor_position() else {
+            continue;
+        };
+        let Some((cam, cam_glob_xform, viewport)) = cam_qry
+            .iter()
+            .filter(|(cam, _)| cam.is_active)
+            .filter(|(cam, _)| {
+                let RenderTarget::Window(win_ref) = &cam.target else {
+                    return false;
+                };
+                win_ref
+                    .normalize(primary_win_id)
+                    .is_some_and(|normalized_win_ref| normalized_win_ref.entity() == win_id)
+            })
+            .filter_map(|(cam, cam_glob_xform)| {
+                let viewport = cam.logical_viewport_rect()?;
+                viewport
+                    .contains(cursor_pos)
+                    .then_some((cam, cam_glob_xform, viewport))
+            })
+            .max_by_key(|(cam, _, _)| cam.order)
+        else {
+            continue;
+        };
+        if let Some(world_pos) = cam.viewport_to_world_2d(cam_glob_xform, cursor_pos - viewport.min)
+        {
+            cursor.world_pos = Some(world_pos);
+            return;
+        }
+    }
+}
+
+fn switch_aim_sources(
+    mut gamepad_evr: EventReader<GamepadConnectionEvent>,
+    mut cursor_moved_evr: EventReader<CursorMoved>,
+    mut aim_qry: Query<(&mut AimSource, &mut AimDevices)>,
+) {
+    for gamepad_ev in gamepad_evr.read() {
+        let gamepad = gamepad_ev.gamepad;
+        match gamepad_ev.connection {
+            GamepadConnection::Connected(_) => {
+                if aim_qry
+                    .iter()
+                    .any(|(_, aim_devices)| aim_devices.gamepad == Some(gamepad))
+                {
+                    continue;
+                }
+                if let Some((mut aim_src, mut aim_devices)) = aim_qry
+                    .iter_mut()
+                    .find(|(_, aim_devices)| aim_devices.gamepad.is_none())
+                {
+                    aim_devices.gamepad = Some(gamepad);
+                    *aim_src = AimSource::Gamepad(gamepad);
+                }
+            }
+            GamepadConnection::Disconnected => {
+                for (mut aim_src, mut aim_devices) in aim_qry.iter_mut() {
+                    if aim_devices.gamepad != Some(gamepad) {
+                        continue;
+                    }
+                    aim_devices.gamepad = None;
+                    if aim_devices.cursor {
+                        *aim_src = AimSource::Cursor;
+                    }
+                }
+            }
+        }
+    }
+    if cursor_moved_evr.read().count() > 0 {
+        for (mut aim_src, aim_devices) in aim_qry.iter_mut() {
+            if aim_devices.cursor && !matches!(*aim_src, AimSource::Cursor) {
+                *aim_src = AimSource::Cursor;
+            }
+        }
+    }
+}
+
+fn aim_with_cursor(cursor: Res<WorldCursor>, mut aim_qry: Query<(&mut AimTarget, &AimSource)>) {
+    let Some(cursor_world_pos) = cursor.world_pos else {
+        return;
+    };
+    for (mut aim_target, aim_src) in aim_qry.iter_mut() {
+        if let AimSource::Cursor = aim_src {
+            aim_target.0 = cursor_world_pos;
+        }
+    }
+}
+
+fn aim_with_gamepad(
+    axes: Res<Axis<GamepadAxis>>,
+    mut aim_qry: Query<(&mut AimTarget, &AimSource, &GlobalTransform)>,
+) {
+    for (mut aim_target, aim_src, glob_xform) in aim_qry.iter_mut() {
+        let AimSource::Gamepad(gamepad) = *aim_src else {
+            continue;
+        };
+        let (Some(x), Some(y)) = (
+            axes.get(GamepadAxis::new(gamepad, GamepadAxisType::RightStickX)),
+            axes.get(GamepadAxis::new(gamepad, GamepadAxisType::RightStickY)),
+        ) else {
+            continue;
+        };
+        let stick = Vec2::new(x, y);
+        if stick.length() > GAMEPAD_AIM_DEADZONE {
+            aim_target.0 =
+                glob_xform.translation().truncate() + stick.normalize() * GAMEPAD_AIM_DISTANCE;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::simulation::Simulation};
+
+    #[test]
+    fn aim_sources_follow_their_own_devices() {
+        let mut sim = Simulation::new();
+        let other_gamepad = Gamepad::new(1);
+        let other_id = sim
+            .app
+            .world
+            .spawn((
+                AimTarget::default(),
+                AimSource::Gamepad(other_gamepad),
+                AimDevices {
+                    cursor: false,
+                    gamepad: Some(other_gamepad),
+                },
+                TransformBundle::default(),
+            ))
+            .id();
+        let other_aim_source =
+            |sim: &Simulation| *sim.app.world.get::<AimSource>(other_id).unwrap();
+
+        let gamepad = Gamepad::new(0);
+        sim.app.world.send_event(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected(GamepadInfo {
+                name: String::from("pad"),
+            }),
+        ));
+        sim.step(&[]);
+        assert!(matches!(
+            sim.player::<AimSource>(),
+            AimSource::Gamepad(player_gamepad) if *player_gamepad == gamepad
+        ));
+        assert!(matches!(
+            other_aim_source(&sim),
+            AimSource::Gamepad(gamepad) if gamepad == other_gamepad
+        ));
+
+        sim.app.world.send_event(CursorMoved {
+            window: Entity::PLACEHOLDER,
+            position: Vec2::ZERO,
+        });
+        sim.step(&[]);
+        assert!(matches!(sim.player::<AimSource>(), AimSource::Cursor));
+        assert!(matches!(other_aim_source(&sim), AimSource::Gamepad(_)));
+
+        sim.app.world.send_event(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Disconnected,
+        ));
+        sim.step(&[]);
+        assert_eq!(sim.player::<AimDevices>().gamepad, None);
+        assert_eq!(
+            sim.app.world.get::<AimDevices>(other_id).unwrap().gamepad,
+            Some(other_gamepad)
+        );
+    }
+}
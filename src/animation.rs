@@ -1,4 +1,4 @@
-use bevy::prelude::*;
+use {super::time_control::TimeScale, bevy::prelude::*};
 
 pub struct AnimationPlugin;
 
@@ -34,10 +34,14 @@ fn animate_sprites(
         &AnimationIndices,
         &mut AnimationTimer,
         &mut TextureAtlasSprite,
+        Option<&TimeScale>,
     )>,
 ) {
-    for (animation_indices, mut animation_timer, mut tex_atlas_sprite) in animation_qry.iter_mut() {
-        animation_timer.tick(time.delta());
+    for (animation_indices, mut animation_timer, mut tex_atlas_sprite, time_scale) in
+        animation_qry.iter_mut()
+    {
+        let scale = time_scale.map_or(1., |time_scale| time_scale.0);
+        animation_timer.tick(time.delta().mul_f32(scale));
         if animation_timer.just_finished() {
             tex_atlas_sprite.index = if tex_atlas_sprite.index == animation_indices.last {
                 animation_indices.first
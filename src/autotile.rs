@@ -0,0 +1,207 @@
+use {
+    super::{
+        floor,
+        game_state::GameState,
+        ron_asset::RonAssetPlugin,
+        tile::{self, TileWorld, Tileset},
+    },
+    bevy::{prelude::*, utils::HashSet},
+    bevy_ecs_tilemap::prelude::*,
+    serde::Deserialize,
+};
+
+pub struct AutotilePlugin;
+
+impl Plugin for AutotilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<AutotileRules>::new(&["autotile.ron"]))
+            .add_systems(Startup, load_autotile_rules)
+            .add_systems(
+                PostUpdate,
+                autotile
+                    .after(floor::stream_rooms)
+                    .before(tile::stream_chunks)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Neighbour {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
+impl Neighbour {
+    const ALL: [Self; 8] = [
+        Self::N,
+        Self::NE,
+        Self::E,
+        Self::SE,
+        Self::S,
+        Self::SW,
+        Self::W,
+        Self::NW,
+    ];
+
+    fn offset(self) -> IVec2 {
+        match self {
+            Self::N => IVec2::new(0, 1),
+            Self::NE => IVec2::new(1, 1),
+            Self::E => IVec2::new(1, 0),
+            Self::SE => IVec2::new(1, -1),
+            Self::S => IVec2::new(0, -1),
+            Self::SW => IVec2::new(-1, -1),
+            Self::W => IVec2::new(-1, 0),
+            Self::NW => IVec2::new(-1, 1),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AutotileRule {
+    #[serde(default)]
+    pub solid: Vec<Neighbour>,
+    #[serde(default)]
+    pub empty: Vec<Neighbour>,
+    pub texture: u32,
+}
+
+#[derive(Asset, TypePath, Deserialize)]
+pub struct AutotileRules {
+    pub tileset: String,
+    #[serde(default)]
+    pub fallback: u32,
+    pub rules: Vec<AutotileRule>,
+}
+
+impl AutotileRules {
+    pub fn texture(&self, tile_world: &TileWorld, tile: IVec2) -> TileTextureIndex {
+        let solid = |neighbour: &Neighbour| tile_world.get(tile + neighbour.offset()).is_some();
+        TileTextureIndex(
+            self.rules
+                .iter()
+                .find(|rule| rule.solid.iter().all(solid) && !rule.empty.iter().any(solid))
+                .map_or(self.fallback, |rule| rule.texture),
+        )
+    }
+}
+
+#[derive(Resource, Deref)]
+pub struct AutotileRulesHandle(pub Handle<AutotileRules>);
+
+fn load_autotile_rules(mut cmds: Commands, asset_server: Res<AssetServer>) {
+    cmds.insert_resource(AutotileRulesHandle(
+        asset_server.load("data/base.autotile.ron"),
+    ));
+}
+
+fn autotile(
+    mut loaded: Local<bool>,
+    asset_server: Res<AssetServer>,
+    rules_handle: Res<AutotileRulesHandle>,
+    rules: Res<Assets<AutotileRules>>,
+    mut rules_evr: EventReader<AssetEvent<AutotileRules>>,
+    mut tile_world: ResMut<TileWorld>,
+    mut tileset: ResMut<Tileset>,
+    mut tilemap_qry: Query<&mut TilemapTexture>,
+) {
+    let Some(rules) = rules.get(&rules_handle.0) else {
+        return;
+    };
+    let modified = rules_evr
+        .read()
+        .any(|rules_ev| rules_ev.is_modified(&rules_handle.0));
+
+    let tiles = if !*loaded || modified {
+        *loaded = true;
+        tileset.0 = asset_server.load(&rules.tileset);
+        for mut tilemap_tex in tilemap_qry.iter_mut() {
+            *tilemap_tex = TilemapTexture::Single(tileset.0.clone());
+        }
+        tile_world.tiles().collect::<HashSet<_>>()
+    } else {
+        tile_world
+            .changed()
+            .flat_map(|tile| {
+                Neighbour::ALL
+                    .iter()
+                    .map(move |neighbour| tile + neighbour.offset())
+                    .chain([tile])
+            })
+            .collect()
+    };
+
+    for tile in tiles {
+        if tile_world.get(tile).is_some() {
+            let tex_idx = rules.texture(&tile_world, tile);
+            tile_world.set(tile, tex_idx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::simulation::Simulation, std::fs};
+
+    fn base_rules() -> AutotileRules {
+        ron::de::from_str(&fs::read_to_string("assets/data/base.autotile.ron").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn textures_follow_cardinal_neighbours() {
+        let rules = base_rules();
+        let mut tile_world = TileWorld::default();
+        for y in 0..3 {
+            for x in 0..3 {
+                tile_world.set(IVec2::new(x, y), TileTextureIndex(0));
+            }
+        }
+        tile_world.set(IVec2::new(5, 5), TileTextureIndex(0));
+
+        let texture = |tile: IVec2| rules.texture(&tile_world, tile).0;
+        assert_eq!(texture(IVec2::new(1, 1)), 15);
+        assert_eq!(texture(IVec2::new(1, 2)), 14);
+        assert_eq!(texture(IVec2::new(0, 0)), 3);
+        assert_eq!(texture(IVec2::new(2, 1)), 13);
+        assert_eq!(texture(IVec2::new(5, 5)), 0);
+    }
+
+    #[test]
+    fn textures_update_around_changed_tiles() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let player_tile = tile::tile_at(sim.player::<Transform>().translation.truncate());
+        let ground = (1..)
+            .map(|depth| player_tile - IVec2::Y * depth)
+            .find(|&tile| sim.app.world.resource::<TileWorld>().get(tile).is_some())
+            .unwrap();
+        let texture = |sim: &Simulation, tile: IVec2| {
+            sim.app.world.resource::<TileWorld>().get(tile).unwrap().0
+        };
+        assert_eq!(texture(&sim, ground) & 1, 0);
+
+        let below = (3..)
+            .map(|dx| ground + IVec2::X * dx)
+            .find(|&tile| {
+                let tile_world = sim.app.world.resource::<TileWorld>();
+                tile_world.get(tile).is_some() && tile_world.get(tile + IVec2::Y).is_none()
+            })
+            .unwrap();
+        assert_eq!(texture(&sim, below) & 1, 0);
+
+        sim.app.world.send_event(tile::PlaceTile {
+            tile: below + IVec2::Y,
+            tex_idx: TileTextureIndex(0),
+        });
+        sim.step(&[]);
+        assert_eq!(texture(&sim, below) & 1, 1);
+        assert_eq!(texture(&sim, below + IVec2::Y) & 4, 4);
+    }
+}
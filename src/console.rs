@@ -0,0 +1,380 @@
+#![cfg_attr(not(feature = "dev"), allow(dead_code))]
+
+use {
+    super::{cli, game_state::GameState, player::PlayerAction},
+    bevy::prelude::*,
+    leafwing_input_manager::prelude::*,
+    std::{
+        collections::{BTreeMap, VecDeque},
+        fs,
+    },
+};
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        let mut script = ConsoleScript::default();
+        if let Some(path) = cli::arg_value("--exec") {
+            match fs::read_to_string(&path) {
+                Ok(text) => {
+                    script.0 = text
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                        .map(String::from)
+                        .collect()
+                }
+                Err(err) => error!("failed to read console script {path}: {err}"),
+            }
+        }
+
+        app.init_resource::<Console>()
+            .insert_resource(script)
+            .add_console_command("help", "list available commands", help)
+            .add_console_command("clear", "clear the console log", clear)
+            .add_console_command("state", "state <name>: switch game state", set_state)
+            .add_systems(Startup, spawn_console)
+            .add_systems(
+                Update,
+                (
+                    toggle_console,
+                    edit_console_input.run_if(console_open),
+                    run_console_script.run_if(in_state(GameState::Playing)),
+                    run_console_commands,
+                    update_console_text,
+                )
+                    .chain(),
+            );
+    }
+}
+
+const MAX_LOG_LINES: usize = 12;
+
+pub type ConsoleCommandFn = fn(&mut World, &[&str]) -> Result<String, String>;
+
+#[derive(Clone, Copy)]
+pub struct ConsoleCommand {
+    pub help: &'static str,
+    pub run: ConsoleCommandFn,
+}
+
+#[derive(Resource, Default)]
+pub struct ConsoleCommands(pub BTreeMap<&'static str, ConsoleCommand>);
+
+pub trait AddConsoleCommand {
+    fn add_console_command(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        run: ConsoleCommandFn,
+    ) -> &mut Self;
+}
+
+impl AddConsoleCommand for App {
+    fn add_console_command(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        run: ConsoleCommandFn,
+    ) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(ConsoleCommands::default)
+            .0
+            .insert(name, ConsoleCommand { help, run });
+        self
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Console {
+    pub open: bool,
+    pub input: String,
+    pub log: Vec<String>,
+    history: Vec<String>,
+    history_idx: Option<usize>,
+    queue: VecDeque<String>,
+}
+
+impl Console {
+    pub fn run(&mut self, line: impl Into<String>) {
+        self.queue.push_back(line.into());
+    }
+
+    fn print(&mut self, line: impl Into<String>) {
+        let line = line.into();
+        info!("{line}");
+        self.log.push(line);
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ConsoleScript(pub VecDeque<String>);
+
+#[derive(Component)]
+struct ConsoleRoot;
+
+#[derive(Component)]
+struct ConsoleLog;
+
+#[derive(Component)]
+struct ConsoleInput;
+
+fn console_open(console: Res<Console>) -> bool {
+    console.open
+}
+
+fn spawn_console(mut cmds: Commands) {
+    let text_style = TextStyle {
+        font_size: 14.,
+        color: Color::WHITE,
+        ..default()
+    };
+
+    cmds.spawn((
+        ConsoleRoot,
+        Name::new("Console"),
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(40.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::FlexEnd,
+                padding: UiRect::all(Val::Px(4.)),
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.8).into(),
+            visibility: Visibility::Hidden,
+            z_index: ZIndex::Global(i32::MAX),
+            ..default()
+        },
+    ))
+    .with_children(|parent| {
+        parent.spawn((ConsoleLog, TextBundle::from_section("", text_style.clone())));
+        parent.spawn((ConsoleInput, TextBundle::from_section("> ", text_style)));
+    });
+}
+
+pub fn toggle_console(
+    keys: Res<Input<KeyCode>>,
+    mut console: ResMut<Console>,
+    mut console_root_qry: Query<&mut Visibility, With<ConsoleRoot>>,
+    toggle_actions: Option<ResMut<ToggleActions<PlayerAction>>>,
+) {
+    if !(keys.just_pressed(KeyCode::Grave) || (console.open && keys.just_pressed(KeyCode::Escape)))
+    {
+        return;
+    }
+    console.open = !console.open;
+    for mut console_root_vis in console_root_qry.iter_mut() {
+        *console_root_vis = if console.open {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+    if let Some(mut toggle_actions) = toggle_actions {
+        toggle_actions.enabled = !console.open;
+    }
+}
+
+fn edit_console_input(
+    keys: Res<Input<KeyCode>>,
+    mut char_evr: EventReader<ReceivedCharacter>,
+    mut console: ResMut<Console>,
+    cmds: Res<ConsoleCommands>,
+) {
+    for char_ev in char_evr.read() {
+        if !char_ev.char.is_control() && char_ev.char != '`' {
+            console.input.push(char_ev.char);
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut console.input);
+        if !line.trim().is_empty() {
+            console.history.push(line.clone());
+            console.run(line);
+        }
+        console.history_idx = None;
+    }
+    if keys.just_pressed(KeyCode::Up) && !console.history.is_empty() {
+        let idx = console
+            .history_idx
+            .map_or(console.history.len() - 1, |idx| idx.saturating_sub(1));
+        console.history_idx = Some(idx);
+        console.input = console.history[idx].clone();
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        if let Some(idx) = console.history_idx {
+            if idx + 1 < console.history.len() {
+                console.history_idx = Some(idx + 1);
+                console.input = console.history[idx + 1].clone();
+            } else {
+                console.history_idx = None;
+                console.input.clear();
+            }
+        }
+    }
+    if keys.just_pressed(KeyCode::Tab) && !console.input.contains(' ') {
+        let candidates = cmds
+            .0
+            .keys()
+            .filter(|name| name.starts_with(console.input.as_str()))
+            .copied()
+            .collect::<Vec<_>>();
+        match candidates.as_slice() {
+            [] => {}
+            [name] => console.input = format!("{name} "),
+            [first, rest @ ..] => {
+                let common_len = rest.iter().fold(first.len(), |len, name| {
+                    first
+                        .bytes()
+                        .zip(name.bytes())
+                        .take(len)
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                });
+                console.input = first[..common_len].to_owned();
+                console.print(candidates.join("  "));
+            }
+        }
+    }
+}
+
+fn run_console_script(mut script: ResMut<ConsoleScript>, mut console: ResMut<Console>) {
+    if let Some(line) = script.0.pop_front() {
+        console.run(line);
+    }
+}
+
+fn run_console_commands(world: &mut World) {
+    let lines = world
+        .resource_mut::<Console>()
+        .queue
+        .drain(..)
+        .collect::<Vec<_>>();
+
+    for line in lines {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let Some((&name, args)) = words.split_first() else {
+            continue;
+        };
+        world.resource_mut::<Console>().print(format!("> {line}"));
+        let cmd = world.resource::<ConsoleCommands>().0.get(name).copied();
+        let output = match cmd {
+            Some(cmd) => (cmd.run)(world, args),
+            None => Err(format!("unknown command `{name}`")),
+        };
+
+        let mut console = world.resource_mut::<Console>();
+        match output {
+            Ok(msg) if !msg.is_empty() => console.print(msg),
+            Ok(_) => {}
+            Err(err) => console.print(format!("error: {err}")),
+        }
+    }
+}
+
+fn update_console_text(
+    console: Res<Console>,
+    mut console_log_qry: Query<&mut Text, (With<ConsoleLog>, Without<ConsoleInput>)>,
+    mut console_input_qry: Query<&mut Text, (With<ConsoleInput>, Without<ConsoleLog>)>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    for mut console_log_text in console_log_qry.iter_mut() {
+        console_log_text.sections[0].value =
+            console.log[console.log.len().saturating_sub(MAX_LOG_LINES)..].join("\n");
+    }
+    for mut console_input_text in console_input_qry.iter_mut() {
+        console_input_text.sections[0].value = format!("> {}", console.input);
+    }
+}
+
+pub fn parse_args<T: std::str::FromStr>(args: &[&str]) -> Result<Vec<T>, String> {
+    args.iter()
+        .map(|arg| arg.parse().map_err(|_| format!("invalid argument `{arg}`")))
+        .collect()
+}
+
+fn help(world: &mut World, _: &[&str]) -> Result<String, String> {
+    Ok(world
+        .resource::<ConsoleCommands>()
+        .0
+        .iter()
+        .map(|(name, cmd)| format!("{name}: {}", cmd.help))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn clear(world: &mut World, _: &[&str]) -> Result<String, String> {
+    world.resource_mut::<Console>().log.clear();
+    Ok(String::new())
+}
+
+fn set_state(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let [name] = args else {
+        return Err(String::from("usage: state <name>"));
+    };
+    let state = name.parse::<GameState>()?;
+    world.resource_mut::<NextState<GameState>>().set(state);
+    Ok(String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::simulation::Simulation};
+
+    #[test]
+    fn queued_commands_run_against_world() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+
+        let mut console = sim.app.world.resource_mut::<Console>();
+        console.run("tp 40 -50");
+        console.run("frobnicate");
+        sim.step(&[]);
+
+        let player_translation = sim.player::<Transform>().translation;
+        assert_eq!(player_translation.truncate(), Vec2::new(40., -50.));
+        let console = sim.app.world.resource::<Console>();
+        assert_eq!(
+            console.log.last().map(String::as_str),
+            Some("error: unknown command `frobnicate`")
+        );
+    }
+
+    #[test]
+    fn exec_script_waits_for_the_run_and_runs_a_line_per_frame() {
+        let mut sim = Simulation::with(|app| {
+            app.insert_resource(NextState::<GameState>(None))
+                .insert_resource(ConsoleScript(VecDeque::from([
+                    String::from("tp 40 -50"),
+                    String::from("tp 60 -50"),
+                ])));
+        });
+        sim.app.update();
+        assert_eq!(sim.app.world.resource::<ConsoleScript>().0.len(), 2);
+        assert!(sim.app.world.resource::<Console>().log.is_empty());
+
+        sim.app
+            .world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        sim.app.update();
+        let player_translation = sim.player::<Transform>().translation;
+        assert_eq!(player_translation.truncate(), Vec2::new(40., -50.));
+
+        sim.step(&[]);
+        let player_translation = sim.player::<Transform>().translation;
+        assert_eq!(player_translation.truncate(), Vec2::new(60., -50.));
+        let console = sim.app.world.resource::<Console>();
+        assert!(console.log.iter().all(|line| !line.starts_with("error")));
+    }
+}
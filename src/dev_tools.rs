@@ -0,0 +1,76 @@
+use {
+    bevy::{
+        diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+        prelude::*,
+        time::common_conditions::on_timer,
+    },
+    bevy_inspector_egui::quick::WorldInspectorPlugin,
+    bevy_rapier2d::prelude::*,
+    std::time::Duration,
+};
+
+pub struct DevToolsPlugin;
+
+impl Plugin for DevToolsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DevTools>()
+            .add_plugins((
+                WorldInspectorPlugin::new().run_if(|dev_tools: Res<DevTools>| dev_tools.inspector),
+                RapierDebugRenderPlugin::default(),
+                FrameTimeDiagnosticsPlugin,
+            ))
+            .add_systems(
+                Update,
+                (
+                    toggle_dev_tools,
+                    log_diagnostics
+                        .run_if(|dev_tools: Res<DevTools>| dev_tools.diagnostics)
+                        .run_if(on_timer(Duration::from_secs(1))),
+                ),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct DevTools {
+    pub inspector: bool,
+    pub physics_debug: bool,
+    pub diagnostics: bool,
+}
+
+impl Default for DevTools {
+    fn default() -> Self {
+        Self {
+            inspector: true,
+            physics_debug: true,
+            diagnostics: false,
+        }
+    }
+}
+
+fn toggle_dev_tools(
+    keys: Res<Input<KeyCode>>,
+    mut dev_tools: ResMut<DevTools>,
+    mut debug_render_ctx: ResMut<DebugRenderContext>,
+) {
+    if keys.just_pressed(KeyCode::F1) {
+        dev_tools.inspector = !dev_tools.inspector;
+    }
+    if keys.just_pressed(KeyCode::F2) {
+        dev_tools.physics_debug = !dev_tools.physics_debug;
+    }
+    if keys.just_pressed(KeyCode::F3) {
+        dev_tools.diagnostics = !dev_tools.diagnostics;
+    }
+    debug_render_ctx.enabled = dev_tools.physics_debug;
+}
+
+fn log_diagnostics(diagnostics: Res<DiagnosticsStore>) {
+    let Some(fps) = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed())
+    else {
+        return;
+    };
+    info!("fps: {fps:.1}");
+}
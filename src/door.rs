@@ -0,0 +1,441 @@
+use {
+    super::{
+        console::AddConsoleCommand,
+        floor::{self, FloorState, InRoom},
+        game_state::GameState,
+        inventory::{self, Inventory, ItemStack},
+        physics::TILE_GROUP,
+        player::{self, Player, PlayerAction, TickActions},
+        ron_asset::RonAssetPlugin,
+    },
+    bevy::{prelude::*, utils::HashSet},
+    bevy_rapier2d::prelude::*,
+    serde::Deserialize,
+};
+
+pub struct DoorPlugin;
+
+impl Plugin for DoorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<DoorLayout>::new(&["doors.ron"]))
+            .add_event::<OpenDoor>()
+            .add_systems(Startup, load_door_layout)
+            .add_systems(
+                FixedUpdate,
+                (interact, open_doors)
+                    .chain()
+                    .after(player::latch_player_actions)
+                    .distributive_run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                PostUpdate,
+                spawn_room_doors
+                    .before(floor::stream_rooms)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_console_command("open", "open <door>: open a door by id", open);
+    }
+}
+
+const INTERACT_RANGE: f32 = 20.;
+const OPEN_ALPHA: f32 = 0.25;
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+pub enum Lock {
+    #[default]
+    Unlocked,
+    Key(String),
+    Lever,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct DoorDef {
+    pub id: String,
+    #[serde(default)]
+    pub room: [i32; 2],
+    pub pos: [f32; 2],
+    #[serde(default)]
+    pub lock: Lock,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LeverDef {
+    #[serde(default)]
+    pub room: [i32; 2],
+    pub pos: [f32; 2],
+    pub doors: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct KeyDef {
+    pub item: String,
+    #[serde(default)]
+    pub room: [i32; 2],
+    pub pos: [f32; 2],
+}
+
+#[derive(Asset, TypePath, Deserialize)]
+pub struct DoorLayout {
+    #[serde(default)]
+    pub doors: Vec<DoorDef>,
+    #[serde(default)]
+    pub levers: Vec<LeverDef>,
+    #[serde(default)]
+    pub keys: Vec<KeyDef>,
+}
+
+#[derive(Resource, Deref)]
+pub struct DoorLayoutHandle(pub Handle<DoorLayout>);
+
+#[derive(Component)]
+pub struct Door {
+    pub id: String,
+    pub lock: Lock,
+    pub open: bool,
+}
+
+#[derive(Component)]
+pub struct Lever {
+    pub doors: Vec<String>,
+    pub pulled: bool,
+}
+
+#[derive(Event)]
+pub struct OpenDoor {
+    pub id: String,
+}
+
+fn load_door_layout(mut cmds: Commands, asset_server: Res<AssetServer>) {
+    cmds.insert_resource(DoorLayoutHandle(asset_server.load("data/base.doors.ron")));
+}
+
+pub fn spawn_door(cmds: &mut Commands, tex: Handle<Image>, def: &DoorDef, open: bool) -> Entity {
+    let room = IVec2::from_array(def.room);
+    let pos = floor::room_origin(room) + Vec2::from(def.pos);
+    let mut door = cmds.spawn((
+        Name::new(format!("Door ({})", def.id)),
+        Door {
+            id: def.id.clone(),
+            lock: def.lock.clone(),
+            open,
+        },
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::WHITE.with_a(if open { OPEN_ALPHA } else { 1. }),
+                ..default()
+            },
+            texture: tex,
+            transform: Transform::from_translation(pos.extend(1.)),
+            ..default()
+        },
+        InRoom(room),
+    ));
+    if !open {
+        door.insert((
+            Collider::cuboid(8., 16.),
+            CollisionGroups::new(TILE_GROUP, Group::ALL),
+        ));
+    }
+    door.id()
+}
+
+pub fn spawn_lever(
+    cmds: &mut Commands,
+    tex: Handle<Image>,
+    def: &LeverDef,
+    pulled: bool,
+) -> Entity {
+    let room = IVec2::from_array(def.room);
+    let pos = floor::room_origin(room) + Vec2::from(def.pos);
+    cmds.spawn((
+        Name::new("Lever"),
+        Lever {
+            doors: def.doors.clone(),
+            pulled,
+        },
+        SpriteBundle {
+            sprite: Sprite {
+                flip_x: pulled,
+                ..default()
+            },
+            texture: tex,
+            transform: Transform::from_translation(pos.extend(1.)),
+            ..default()
+        },
+        InRoom(room),
+    ))
+    .id()
+}
+
+fn spawn_room_doors(
+    mut cmds: Commands,
+    mut furnished: Local<HashSet<IVec2>>,
+    asset_server: Res<AssetServer>,
+    layout_handle: Res<DoorLayoutHandle>,
+    layouts: Res<Assets<DoorLayout>>,
+    floor_state: Res<FloorState>,
+    player_qry: Query<&Inventory, With<Player>>,
+) {
+    let Some(layout) = layouts.get(&layout_handle.0) else {
+        return;
+    };
+    furnished.retain(|room| floor_state.generated.contains(room));
+
+    for &room in floor_state.generated.iter() {
+        if !furnished.insert(room) {
+            continue;
+        }
+        let in_room = |def_room: [i32; 2]| IVec2::from_array(def_room) == room;
+
+        for door_def in layout
+            .doors
+            .iter()
+            .filter(|door_def| in_room(door_def.room))
+        {
+            spawn_door(
+                &mut cmds,
+                asset_server.load("door.png"),
+                door_def,
+                floor_state.opened_doors.contains(&door_def.id),
+            );
+        }
+        for lever_def in layout
+            .levers
+            .iter()
+            .filter(|lever_def| in_room(lever_def.room))
+        {
+            spawn_lever(
+                &mut cmds,
+                asset_server.load("lever.png"),
+                lever_def,
+                lever_def
+                    .doors
+                    .iter()
+                    .all(|id| floor_state.opened_doors.contains(id)),
+            );
+        }
+        for key_def in layout.keys.iter().filter(|key_def| in_room(key_def.room)) {
+            let used = layout.doors.iter().any(|door_def| {
+                door_def.lock == Lock::Key(key_def.item.clone())
+                    && floor_state.opened_doors.contains(&door_def.id)
+            });
+            let held = player_qry
+                .iter()
+                .any(|inventory| inventory.count(&key_def.item) > 0);
+            if used || held {
+                continue;
+            }
+            let key_id = inventory::spawn_pickup(
+                &mut cmds,
+                ItemStack::new(key_def.item.clone(), 1),
+                floor::room_origin(room) + Vec2::from(key_def.pos),
+                Vec2::ZERO,
+            );
+            cmds.entity(key_id).insert(InRoom(room));
+        }
+    }
+}
+
+fn interact(
+    mut open_door_evw: EventWriter<OpenDoor>,
+    mut player_qry: Query<(&mut Inventory, &TickActions, &Transform), With<Player>>,
+    door_qry: Query<(&Door, &Transform)>,
+    mut lever_qry: Query<(&mut Lever, &mut Sprite, &Transform)>,
+) {
+    for (mut inventory, player_tick_actions, player_xform) in player_qry.iter_mut() {
+        if !player_tick_actions.just_pressed(PlayerAction::Interact) {
+            continue;
+        }
+        let in_range = |xform: &Transform| {
+            xform
+                .translation
+                .truncate()
+                .distance(player_xform.translation.truncate())
+                <= INTERACT_RANGE
+        };
+
+        for (door, _) in door_qry
+            .iter()
+            .filter(|(door, door_xform)| !door.open && in_range(door_xform))
+        {
+            let unlocked = match &door.lock {
+                Lock::Unlocked => true,
+                Lock::Key(item) => inventory.take(item, 1),
+                Lock::Lever => false,
+            };
+            if unlocked {
+                open_door_evw.send(OpenDoor {
+                    id: door.id.clone(),
+                });
+            }
+        }
+        for (mut lever, mut sprite, _) in lever_qry
+            .iter_mut()
+            .filter(|(lever, _, lever_xform)| !lever.pulled && in_range(lever_xform))
+        {
+            lever.pulled = true;
+            sprite.flip_x = true;
+            for id in lever.doors.iter() {
+                open_door_evw.send(OpenDoor { id: id.clone() });
+            }
+        }
+    }
+}
+
+fn open_doors(
+    mut cmds: Commands,
+    mut open_door_evr: EventReader<OpenDoor>,
+    mut floor_state: ResMut<FloorState>,
+    mut door_qry: Query<(Entity, &mut Door, &mut Sprite)>,
+) {
+    for open_door in open_door_evr.read() {
+        floor_state.opened_doors.insert(open_door.id.clone());
+        for (door_id, mut door, mut sprite) in door_qry.iter_mut() {
+            if door.open || door.id != open_door.id {
+                continue;
+            }
+            door.open = true;
+            sprite.color.set_a(OPEN_ALPHA);
+            cmds.entity(door_id).remove::<Collider>();
+        }
+    }
+}
+
+fn open(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let &[id] = args else {
+        return Err(String::from("usage: open <door>"));
+    };
+    if !world.query::<&Door>().iter(world).any(|door| door.id == id) {
+        return Err(format!("unknown door `{id}`"));
+    }
+    world.send_event(OpenDoor {
+        id: String::from(id),
+    });
+    Ok(String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::simulation::Simulation, bevy::ecs::system::RunSystemOnce, std::fs};
+
+    #[test]
+    fn door_layout_parses() {
+        let layout = ron::de::from_str::<DoorLayout>(
+            &fs::read_to_string("assets/data/base.doors.ron").unwrap(),
+        )
+        .unwrap();
+        assert!(layout
+            .levers
+            .iter()
+            .flat_map(|lever_def| lever_def.doors.iter())
+            .all(|id| layout.doors.iter().any(|door_def| &door_def.id == id)));
+    }
+
+    #[test]
+    fn locked_door_blocks_until_key_is_used() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let start = sim.player::<Transform>().translation.truncate();
+        let door_id = sim.app.world.run_system_once(move |mut cmds: Commands| {
+            spawn_door(
+                &mut cmds,
+                Handle::default(),
+                &DoorDef {
+                    id: String::from("vault"),
+                    room: [0, 0],
+                    pos: [start.x + 24., start.y + 6.],
+                    lock: Lock::Key(String::from("key")),
+                },
+                false,
+            )
+        });
+
+        sim.step_n(64, &[PlayerAction::MoveRight]);
+        assert!(sim.player::<Transform>().translation.x < start.x + 16.);
+
+        sim.step(&[PlayerAction::Interact]);
+        assert!(!sim.app.world.get::<Door>(door_id).unwrap().open);
+
+        let mut player_inventory = sim
+            .app
+            .world
+            .query_filtered::<&mut Inventory, With<Player>>()
+            .single_mut(&mut sim.app.world);
+        player_inventory.add(ItemStack::new("key", 1), 9);
+        sim.step(&[]);
+        sim.step(&[PlayerAction::Interact]);
+        sim.step(&[]);
+
+        assert!(sim.app.world.get::<Door>(door_id).unwrap().open);
+        assert!(sim
+            .player::<Inventory>()
+            .slots
+            .iter()
+            .flatten()
+            .all(|stack| stack.item != "key"));
+        sim.step_n(64, &[PlayerAction::MoveRight]);
+        assert!(sim.player::<Transform>().translation.x > start.x + 32.);
+    }
+
+    #[test]
+    fn doors_respawn_with_their_rooms() {
+        let mut sim = Simulation::new();
+        let layout = ron::de::from_str::<DoorLayout>(
+            &fs::read_to_string("assets/data/base.doors.ron").unwrap(),
+        )
+        .unwrap();
+        let layout_handle = sim
+            .app
+            .world
+            .resource_mut::<Assets<DoorLayout>>()
+            .add(layout);
+        sim.app
+            .world
+            .insert_resource(DoorLayoutHandle(layout_handle));
+        sim.step_n(2, &[]);
+        let doors = |sim: &mut Simulation| {
+            sim.app
+                .world
+                .query::<(&Door, Option<&Collider>)>()
+                .iter(&sim.app.world)
+                .map(|(door, collider)| (door.id.clone(), door.open, collider.is_some()))
+                .collect::<HashSet<_>>()
+        };
+        assert_eq!(
+            doors(&mut sim),
+            HashSet::from_iter([
+                (String::from("vault"), false, true),
+                (String::from("gate"), false, true),
+            ])
+        );
+
+        sim.app.world.send_event(OpenDoor {
+            id: String::from("gate"),
+        });
+        sim.step(&[]);
+        assert!(sim
+            .app
+            .world
+            .resource::<FloorState>()
+            .opened_doors
+            .contains("gate"));
+
+        let mut floor_state = sim.app.world.resource_mut::<FloorState>();
+        floor_state.generated.clear();
+        floor_state.populated.clear();
+        sim.step_n(3, &[]);
+        assert_eq!(
+            doors(&mut sim),
+            HashSet::from_iter([
+                (String::from("vault"), false, true),
+                (String::from("gate"), true, false),
+            ])
+        );
+        assert!(sim
+            .app
+            .world
+            .query::<&Lever>()
+            .iter(&sim.app.world)
+            .all(|lever| lever.pulled));
+    }
+}
@@ -0,0 +1,324 @@
+use {
+    super::{
+        console::AddConsoleCommand,
+        game_state::{self, GameState},
+        health::{DamageEvent, Died, Health},
+        loot::DropLoot,
+        physics::{self, Acceleration, Grounded, NetDirection, TerminalVelocity, ACTOR_GROUP},
+        player::{self, Player},
+        ron_asset::RonAssetPlugin,
+        sprite_flip::Flippable,
+        stats::GainXp,
+        status_effect::StatusEffects,
+        time_control::TimeScale,
+    },
+    bevy::{prelude::*, utils::HashMap},
+    bevy_rapier2d::prelude::*,
+    serde::Deserialize,
+};
+
+pub struct EnemyPlugin;
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<EnemyDefs>::new(&["enemies.ron"]))
+            .add_event::<SpawnEnemy>()
+            .add_systems(Startup, load_enemy_defs)
+            .add_systems(game_state::END_RUN, game_state::despawn_with::<Enemy>)
+            .add_systems(
+                Update,
+                (spawn_enemies, despawn_dead_enemies).run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    chase_player
+                        .after(physics::process_collisions)
+                        .before(player::player_movement),
+                    contact_damage,
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_console_command(
+                "spawn",
+                "spawn enemy <kind>: spawn an enemy next to the player",
+                spawn,
+            );
+    }
+}
+
+const AGGRO_RANGE: f32 = 160.;
+const CHASE_DEADZONE: f32 = 2.;
+const CONTACT_RANGE: f32 = 14.;
+const CONTACT_COOLDOWN: f32 = 1.;
+const CONTACT_KNOCKBACK: f32 = 120.;
+const SPAWN_OFFSET: f32 = 32.;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct EnemyDef {
+    pub sprite: String,
+    pub health: u32,
+    pub speed: f32,
+    pub damage: u32,
+    #[serde(default)]
+    pub loot: Option<String>,
+    #[serde(default)]
+    pub xp: u32,
+}
+
+#[derive(Asset, TypePath, Deserialize, Deref)]
+#[serde(transparent)]
+pub struct EnemyDefs(pub HashMap<String, EnemyDef>);
+
+#[derive(Resource, Deref)]
+pub struct EnemyDefsHandle(pub Handle<EnemyDefs>);
+
+#[derive(Component)]
+pub struct Enemy {
+    pub damage: u32,
+    pub loot: Option<String>,
+    pub xp: u32,
+    cooldown: f32,
+}
+
+#[derive(Event)]
+pub struct SpawnEnemy {
+    pub kind: String,
+    pub pos: Vec2,
+}
+
+fn load_enemy_defs(mut cmds: Commands, asset_server: Res<AssetServer>) {
+    cmds.insert_resource(EnemyDefsHandle(asset_server.load("data/base.enemies.ron")));
+}
+
+pub fn spawn_enemy(
+    cmds: &mut Commands,
+    tex: Handle<Image>,
+    kind: &str,
+    def: &EnemyDef,
+    pos: Vec2,
+) -> Entity {
+    cmds.spawn((
+        Name::new(format!("Enemy ({kind})")),
+        Enemy {
+            damage: def.damage,
+            loot: def.loot.clone(),
+            xp: def.xp,
+            cooldown: 0.,
+        },
+        SpriteBundle {
+            texture: tex,
+            transform: Transform::from_translation(pos.extend(2.)),
+            ..default()
+        },
+        (
+            KinematicCharacterController {
+                filter_flags: QueryFilterFlags::EXCLUDE_SENSORS,
+                ..default()
+            },
+            Collider::capsule_y(2., 6.),
+            CollisionGroups::new(ACTOR_GROUP, Group::ALL),
+            Friction::coefficient(3.),
+            Velocity::zero(),
+            TerminalVelocity(Vec2::new(def.speed, 200.)),
+            Acceleration(Vec2::new(300., 500.)),
+            NetDirection { x: 0, y: -1 },
+            Grounded::default(),
+        ),
+        (Health::new(def.health), StatusEffects::default()),
+        Flippable::default(),
+    ))
+    .id()
+}
+
+fn spawn_enemies(
+    mut cmds: Commands,
+    mut spawn_enemy_evr: EventReader<SpawnEnemy>,
+    asset_server: Res<AssetServer>,
+    defs_handle: Res<EnemyDefsHandle>,
+    defs: Res<Assets<EnemyDefs>>,
+) {
+    let Some(defs) = defs.get(&defs_handle.0) else {
+        return;
+    };
+    for spawn_enemy_ev in spawn_enemy_evr.read() {
+        let Some(def) = defs.get(&spawn_enemy_ev.kind) else {
+            warn!("unknown enemy {}", spawn_enemy_ev.kind);
+            continue;
+        };
+        spawn_enemy(
+            &mut cmds,
+            asset_server.load(&def.sprite),
+            &spawn_enemy_ev.kind,
+            def,
+            spawn_enemy_ev.pos,
+        );
+    }
+}
+
+fn chase_player(
+    player_qry: Query<&Transform, With<Player>>,
+    mut enemy_qry: Query<(&Transform, &mut NetDirection, &mut Flippable), With<Enemy>>,
+) {
+    let Ok(player_xform) = player_qry.get_single() else {
+        return;
+    };
+    for (enemy_xform, mut enemy_net_dir, mut enemy_flippable) in enemy_qry.iter_mut() {
+        let delta = (player_xform.translation - enemy_xform.translation).truncate();
+        if delta.length() > AGGRO_RANGE || delta.x.abs() < CHASE_DEADZONE {
+            enemy_net_dir.x = 0;
+            continue;
+        }
+        enemy_net_dir.x = delta.x.signum() as i8;
+        enemy_flippable.flip_x = delta.x < 0.;
+    }
+}
+
+fn contact_damage(
+    time: Res<Time<Fixed>>,
+    mut damage_evw: EventWriter<DamageEvent>,
+    mut enemy_qry: Query<(&mut Enemy, &Transform, Option<&TimeScale>)>,
+    target_qry: Query<(Entity, &Transform), (With<Player>, With<Health>)>,
+) {
+    for (mut enemy, enemy_xform, time_scale) in enemy_qry.iter_mut() {
+        let scale = time_scale.map_or(1., |time_scale| time_scale.0);
+        enemy.cooldown = (enemy.cooldown - time.timestep().as_secs_f32() * scale).max(0.);
+        if enemy.cooldown > 0. {
+            continue;
+        }
+        for (target_id, target_xform) in target_qry.iter() {
+            let delta = (target_xform.translation - enemy_xform.translation).truncate();
+            if delta.length() > CONTACT_RANGE {
+                continue;
+            }
+            enemy.cooldown = CONTACT_COOLDOWN;
+            damage_evw.send(DamageEvent {
+                target: target_id,
+                amount: enemy.damage,
+                knockback: delta.normalize_or_zero() * CONTACT_KNOCKBACK,
+            });
+            break;
+        }
+    }
+}
+
+fn despawn_dead_enemies(
+    mut cmds: Commands,
+    mut died_evr: EventReader<Died>,
+    mut drop_loot_evw: EventWriter<DropLoot>,
+    mut gain_xp_evw: EventWriter<GainXp>,
+    enemy_qry: Query<(&Enemy, &Transform)>,
+    player_qry: Query<Entity, With<Player>>,
+) {
+    for died in died_evr.read() {
+        let Ok((enemy, enemy_xform)) = enemy_qry.get(died.entity) else {
+            continue;
+        };
+        if let Some(table) = enemy.loot.clone() {
+            drop_loot_evw.send(DropLoot {
+                table,
+                pos: enemy_xform.translation.truncate(),
+            });
+        }
+        if let Ok(player_id) = player_qry.get_single() {
+            gain_xp_evw.send(GainXp {
+                target: player_id,
+                amount: enemy.xp,
+            });
+        }
+        cmds.entity(died.entity).despawn_recursive();
+    }
+}
+
+fn spawn(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let &["enemy", kind] = args else {
+        return Err(String::from("usage: spawn enemy <kind>"));
+    };
+    let defs_handle = world.resource::<EnemyDefsHandle>().0.clone();
+    let defs = world
+        .resource::<Assets<EnemyDefs>>()
+        .get(&defs_handle)
+        .ok_or_else(|| String::from("enemy definitions are still loading"))?;
+    if !defs.contains_key(kind) {
+        let mut kinds = defs.keys().map(String::as_str).collect::<Vec<_>>();
+        kinds.sort_unstable();
+        return Err(format!(
+            "unknown enemy `{kind}` (expected one of {})",
+            kinds.join(", ")
+        ));
+    }
+    let (player_xform, player_flippable) = world
+        .query_filtered::<(&Transform, &Flippable), With<Player>>()
+        .get_single(world)
+        .map_err(|_| String::from("no player"))?;
+
+    let facing = if player_flippable.flip_x { -1. } else { 1. };
+    let pos = player_xform.translation.truncate() + Vec2::X * facing * SPAWN_OFFSET;
+    world.send_event(SpawnEnemy {
+        kind: String::from(kind),
+        pos,
+    });
+    Ok(format!("spawned {kind}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{console::Console, simulation::Simulation, stats::Experience},
+        std::fs,
+    };
+
+    #[test]
+    fn enemy_defs_parse() {
+        let defs = ron::de::from_str::<EnemyDefs>(
+            &fs::read_to_string("assets/data/base.enemies.ron").unwrap(),
+        )
+        .unwrap();
+        assert!(defs.contains_key("slime"));
+    }
+
+    #[test]
+    fn spawned_enemies_chase_hurt_and_die() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let defs = ron::de::from_str::<EnemyDefs>(
+            &fs::read_to_string("assets/data/base.enemies.ron").unwrap(),
+        )
+        .unwrap();
+        let slime_xp = defs["slime"].xp;
+        assert!(slime_xp > 0);
+        let defs_handle = sim.app.world.resource_mut::<Assets<EnemyDefs>>().add(defs);
+        sim.app.world.insert_resource(EnemyDefsHandle(defs_handle));
+
+        let mut console = sim.app.world.resource_mut::<Console>();
+        console.run("spawn enemy slime");
+        console.run("spawn enemy dragon");
+        sim.step_n(2, &[]);
+        let enemy_id = sim
+            .app
+            .world
+            .query_filtered::<Entity, With<Enemy>>()
+            .single(&sim.app.world);
+        assert!(sim
+            .app
+            .world
+            .resource::<Console>()
+            .log
+            .last()
+            .is_some_and(|line| line.starts_with("error: unknown enemy `dragon`")));
+
+        sim.step_n(128, &[]);
+        assert!(sim.player::<Health>().current < sim.player::<Health>().max);
+
+        sim.app.world.send_event(DamageEvent {
+            target: enemy_id,
+            amount: 99,
+            knockback: Vec2::ZERO,
+        });
+        sim.step_n(2, &[]);
+        assert!(sim.app.world.get_entity(enemy_id).is_none());
+        sim.step(&[]);
+        assert_eq!(sim.player::<Experience>().xp, slime_xp);
+    }
+}
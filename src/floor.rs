@@ -0,0 +1,635 @@
+use {
+    super::{
+        console::AddConsoleCommand,
+        enemy::{self, Enemy, EnemyDefs, EnemyDefsHandle},
+        game_state::{self, GameState},
+        loot::DropLoot,
+        player::{Player, PlayerCamera},
+        rng::{RunSeed, SeededRng},
+        tile::{self, TileWorld, TILEMAP_SIZE, TILE_SIZE},
+    },
+    bevy::{
+        prelude::*,
+        transform::TransformSystem,
+        utils::{HashMap, HashSet},
+    },
+    bevy_ecs_tilemap::prelude::*,
+    bevy_rapier2d::prelude::*,
+    serde::{Deserialize, Serialize},
+};
+
+pub struct FloorPlugin;
+
+impl Plugin for FloorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FloorLayout>()
+            .init_resource::<FloorState>()
+            .init_resource::<RoomCamera>()
+            .add_event::<RoomChanged>()
+            .add_systems(
+                game_state::END_RUN,
+                (reset_floor, game_state::despawn_with::<InRoom>),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    (
+                        generate_floor_layout,
+                        stream_rooms,
+                        populate_rooms,
+                        visit_rooms,
+                        clear_rooms,
+                    )
+                        .chain()
+                        .before(PhysicsSet::SyncBackend),
+                    follow_room_camera
+                        .after(PhysicsSet::Writeback)
+                        .before(TransformSystem::TransformPropagate),
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_console_command("descend", "descend: move on to the next floor", descend);
+    }
+}
+
+pub const ROOM_EXTENT: f32 = TILEMAP_SIZE.x as f32 * TILE_SIZE.x;
+const ROOM_COUNT: usize = 8;
+const ROOM_LOOT_TABLE: &str = "chest";
+const ROOM_ENEMIES: [&str; 2] = ["slime", "skeleton"];
+const MAX_ROOM_ENEMIES: u32 = 3;
+const ENEMY_MIN_TILES: u32 = 4;
+const ENEMY_MAX_TILES: u32 = 12;
+const ROOM_TRANSITION_SECONDS: f32 = 0.4;
+const FLOOR_STREAM: u64 = 0x666c_6f6f_72;
+const DEPTH_STREAM: u64 = 0x6465_7074_68;
+const DIRECTIONS: [IVec2; 4] = [IVec2::NEG_X, IVec2::X, IVec2::NEG_Y, IVec2::Y];
+
+#[derive(Resource, Default)]
+pub struct FloorLayout {
+    pub seed: Option<u64>,
+    pub rooms: HashMap<IVec2, Vec<IVec2>>,
+}
+
+impl FloorLayout {
+    pub fn generate(seed: u64) -> Self {
+        let mut rng = SeededRng::new(seed ^ FLOOR_STREAM);
+        let mut rooms = HashMap::from_iter([(IVec2::ZERO, Vec::new())]);
+        let mut order = vec![IVec2::ZERO];
+
+        while rooms.len() < ROOM_COUNT {
+            let from = order[rng.range(0..order.len() as u32) as usize];
+            let dir = DIRECTIONS[rng.range(0..DIRECTIONS.len() as u32) as usize];
+            let to = from + dir;
+            if rooms.contains_key(&to) || (from == IVec2::ZERO && dir.y != 0) {
+                continue;
+            }
+            rooms.get_mut(&from).unwrap().push(dir);
+            rooms.insert(to, vec![-dir]);
+            order.push(to);
+        }
+        Self {
+            seed: Some(seed),
+            rooms,
+        }
+    }
+
+    pub fn neighbours(&self, room: IVec2) -> impl Iterator<Item = IVec2> + '_ {
+        self.rooms
+            .get(&room)
+            .into_iter()
+            .flatten()
+            .map(move |&dir| room + dir)
+    }
+
+    pub fn room_seed(&self, room: IVec2) -> u64 {
+        self.seed.unwrap_or_default()
+            ^ (room.x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            ^ (room.y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RoomState {
+    pub visited: bool,
+    pub cleared: bool,
+    pub looted: bool,
+    #[serde(default)]
+    pub hostile: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct FloorState {
+    pub depth: u32,
+    pub current: IVec2,
+    pub rooms: HashMap<IVec2, RoomState>,
+    pub generated: HashSet<IVec2>,
+    pub populated: HashSet<IVec2>,
+    pub snapshots: HashMap<IVec2, Vec<(IVec2, TileTextureIndex)>>,
+    pub explored: HashSet<IVec2>,
+    pub opened_doors: HashSet<String>,
+}
+
+impl FloorState {
+    pub fn reset(&mut self) {
+        self.current = IVec2::ZERO;
+        self.rooms.clear();
+        self.generated.clear();
+        self.populated.clear();
+        self.snapshots.clear();
+        self.explored.clear();
+        self.opened_doors.clear();
+    }
+}
+
+#[derive(Component, Clone, Copy, Deref)]
+pub struct InRoom(pub IVec2);
+
+#[derive(Event)]
+pub struct RoomChanged {
+    pub from: IVec2,
+    pub to: IVec2,
+}
+
+#[derive(Resource, Default)]
+pub struct RoomCamera {
+    from: Vec2,
+    transition: Option<Timer>,
+}
+
+pub fn room_origin(room: IVec2) -> Vec2 {
+    room.as_vec2() * ROOM_EXTENT
+}
+
+pub fn room_tile_origin(room: IVec2) -> IVec2 {
+    room * IVec2::new(TILEMAP_SIZE.x as i32, TILEMAP_SIZE.y as i32)
+        - IVec2::new(TILEMAP_SIZE.x as i32, TILEMAP_SIZE.y as i32) / 2
+}
+
+pub fn room_at(pos: Vec2) -> IVec2 {
+    ((pos + ROOM_EXTENT / 2.) / ROOM_EXTENT).floor().as_ivec2()
+}
+
+pub fn floor_seed(seed: u64, depth: u32) -> u64 {
+    seed ^ (depth as u64).wrapping_mul(DEPTH_STREAM)
+}
+
+fn reset_floor(mut floor_state: ResMut<FloorState>, mut room_cam: ResMut<RoomCamera>) {
+    *floor_state = FloorState::default();
+    *room_cam = RoomCamera::default();
+}
+
+fn generate_floor_layout(
+    mut layout: ResMut<FloorLayout>,
+    seed: Res<RunSeed>,
+    floor_state: Res<FloorState>,
+) {
+    let seed = floor_seed(seed.0, floor_state.depth);
+    if layout.seed != Some(seed) {
+        *layout = FloorLayout::generate(seed);
+    }
+}
+
+pub fn room_snapshot(
+    layout: &FloorLayout,
+    tile_world: &TileWorld,
+    room: IVec2,
+) -> Option<Vec<(IVec2, TileTextureIndex)>> {
+    let doorways = layout.rooms.get(&room)?;
+    let origin = room_tile_origin(room);
+    let tiles = tile_world
+        .tiles_in(origin, TILEMAP_SIZE)
+        .collect::<Vec<_>>();
+    let generated = tile::generate_tiles(TILEMAP_SIZE, doorways)
+        .into_iter()
+        .map(|tile_pos| origin + IVec2::new(tile_pos.x as i32, tile_pos.y as i32))
+        .collect::<HashSet<_>>();
+    let current = tiles.iter().map(|&(tile, _)| tile).collect::<HashSet<_>>();
+    (current != generated).then_some(tiles)
+}
+
+pub fn stream_rooms(
+    mut cmds: Commands,
+    layout: Res<FloorLayout>,
+    mut floor_state: ResMut<FloorState>,
+    mut tile_world: ResMut<TileWorld>,
+    mut room_changed_evw: EventWriter<RoomChanged>,
+    player_qry: Query<&Transform, With<Player>>,
+    in_room_qry: Query<(Entity, &InRoom)>,
+) {
+    if let Ok(player_xform) = player_qry.get_single() {
+        let room = room_at(player_xform.translation.truncate());
+        if room != floor_state.current && layout.rooms.contains_key(&room) {
+            room_changed_evw.send(RoomChanged {
+                from: floor_state.current,
+                to: room,
+            });
+            floor_state.current = room;
+        }
+    }
+
+    let wanted = layout
+        .neighbours(floor_state.current)
+        .chain([floor_state.current])
+        .collect::<HashSet<_>>();
+    let unloaded = floor_state
+        .generated
+        .difference(&wanted)
+        .copied()
+        .collect::<Vec<_>>();
+    for room in unloaded {
+        if let Some(snapshot) = room_snapshot(&layout, &tile_world, room) {
+            floor_state.snapshots.insert(room, snapshot);
+        }
+        let tiles = tile_world
+            .tiles_in(room_tile_origin(room), TILEMAP_SIZE)
+            .map(|(tile, _)| tile)
+            .collect::<Vec<_>>();
+        for tile in tiles {
+            tile_world.remove(tile);
+        }
+        floor_state.generated.remove(&room);
+        floor_state.populated.remove(&room);
+    }
+    for (entity, in_room) in in_room_qry.iter() {
+        if !floor_state.generated.contains(&in_room.0) {
+            cmds.entity(entity).despawn_recursive();
+        }
+    }
+
+    for room in wanted {
+        let Some(doorways) = layout.rooms.get(&room) else {
+            continue;
+        };
+        if floor_state.generated.contains(&room) {
+            continue;
+        }
+        floor_state.generated.insert(room);
+        if let Some(snapshot) = floor_state.snapshots.remove(&room) {
+            for (tile, tex_idx) in snapshot {
+                tile_world.set(tile, tex_idx);
+            }
+            continue;
+        }
+        let origin = room_tile_origin(room);
+        for tile_pos in tile::generate_tiles(TILEMAP_SIZE, doorways) {
+            tile_world.set(
+                origin + IVec2::new(tile_pos.x as i32, tile_pos.y as i32),
+                TileTextureIndex::default(),
+            );
+        }
+    }
+}
+
+fn populate_rooms(
+    mut cmds: Commands,
+    asset_server: Res<AssetServer>,
+    defs_handle: Res<EnemyDefsHandle>,
+    defs: Res<Assets<EnemyDefs>>,
+    layout: Res<FloorLayout>,
+    mut floor_state: ResMut<FloorState>,
+) {
+    let Some(defs) = defs.get(&defs_handle.0) else {
+        return;
+    };
+    let unpopulated = floor_state
+        .generated
+        .difference(&floor_state.populated)
+        .copied()
+        .collect::<Vec<_>>();
+    for room in unpopulated {
+        floor_state.populated.insert(room);
+        if room == IVec2::ZERO
+            || floor_state
+                .rooms
+                .get(&room)
+                .is_some_and(|room_state| room_state.cleared)
+        {
+            continue;
+        }
+        floor_state.rooms.entry(room).or_default().hostile = true;
+
+        let mut rng = SeededRng::new(layout.room_seed(room));
+        let origin = room_origin(room);
+        for _ in 0..rng.range(1..MAX_ROOM_ENEMIES + 1) {
+            let kind = ROOM_ENEMIES[rng.range(0..ROOM_ENEMIES.len() as u32) as usize];
+            let side = if rng.range(0..2) == 0 { -1. } else { 1. };
+            let x = side * rng.range(ENEMY_MIN_TILES..ENEMY_MAX_TILES) as f32 * TILE_SIZE.x;
+            let Some(def) = defs.get(kind) else {
+                continue;
+            };
+            let enemy_id = enemy::spawn_enemy(
+                &mut cmds,
+                asset_server.load(&def.sprite),
+                kind,
+                def,
+                origin + Vec2::new(x, -ROOM_EXTENT / 2. + 2. * TILE_SIZE.y),
+            );
+            cmds.entity(enemy_id).insert(InRoom(room));
+        }
+    }
+}
+
+fn visit_rooms(
+    mut floor_state: ResMut<FloorState>,
+    mut room_changed_evr: EventReader<RoomChanged>,
+) {
+    for room_changed in room_changed_evr.read() {
+        info!(
+            "entered room {} from {}",
+            room_changed.to, room_changed.from
+        );
+    }
+    let current = floor_state.current;
+    if !floor_state
+        .rooms
+        .get(&current)
+        .is_some_and(|room_state| room_state.visited)
+    {
+        floor_state.rooms.entry(current).or_default().visited = true;
+    }
+}
+
+fn clear_rooms(
+    mut floor_state: ResMut<FloorState>,
+    mut drop_loot_evw: EventWriter<DropLoot>,
+    enemy_qry: Query<&Transform, With<Enemy>>,
+) {
+    let current = floor_state.current;
+    let origin = room_origin(current);
+    if !floor_state.populated.contains(&current) {
+        return;
+    }
+    if !floor_state
+        .rooms
+        .get(&current)
+        .is_some_and(|room_state| !room_state.cleared && room_state.hostile)
+    {
+        return;
+    }
+    if enemy_qry
+        .iter()
+        .any(|enemy_xform| room_at(enemy_xform.translation.truncate()) == current)
+    {
+        return;
+    }
+
+    let Some(room_state) = floor_state.rooms.get_mut(&current) else {
+        return;
+    };
+    room_state.cleared = true;
+    if !room_state.looted {
+        room_state.looted = true;
+        drop_loot_evw.send(DropLoot {
+            table: String::from(ROOM_LOOT_TABLE),
+            pos: origin + Vec2::new(0., -ROOM_EXTENT / 2. + 3. * TILE_SIZE.y),
+        });
+    }
+}
+
+fn follow_room_camera(
+    time: Res<Time>,
+    floor_state: Res<FloorState>,
+    mut room_cam: ResMut<RoomCamera>,
+    mut room_changed_evr: EventReader<RoomChanged>,
+    player_qry: Query<&Transform, (With<Player>, Without<PlayerCamera>)>,
+    mut cam_qry: Query<
+        (&mut Transform, &GlobalTransform, &OrthographicProjection),
+        With<PlayerCamera>,
+    >,
+) {
+    let (Ok(player_xform), Ok((mut cam_xform, cam_glob_xform, cam_projection))) =
+        (player_qry.get_single(), cam_qry.get_single_mut())
+    else {
+        return;
+    };
+    if room_changed_evr.read().last().is_some() {
+        room_cam.from = cam_glob_xform.translation().truncate();
+        room_cam.transition = Some(Timer::from_seconds(
+            ROOM_TRANSITION_SECONDS,
+            TimerMode::Once,
+        ));
+    }
+
+    let player_pos = player_xform.translation.truncate();
+    let origin = room_origin(floor_state.current);
+    let half_room = Vec2::splat(ROOM_EXTENT / 2.);
+    let half_view = cam_projection.area.half_size().min(half_room);
+    let target = player_pos.clamp(
+        origin - half_room + half_view,
+        origin + half_room - half_view,
+    );
+
+    let from = room_cam.from;
+    let pos = match room_cam.transition.as_mut() {
+        Some(transition) => {
+            let t = transition.tick(time.delta()).percent();
+            from.lerp(target, t * t * (3. - 2. * t))
+        }
+        None => target,
+    };
+    if room_cam
+        .transition
+        .as_ref()
+        .is_some_and(|transition| transition.finished())
+    {
+        room_cam.transition = None;
+    }
+    cam_xform.translation = (pos - player_pos).extend(cam_xform.translation.z);
+}
+
+fn descend(world: &mut World, _: &[&str]) -> Result<String, String> {
+    let mut floor_state = world.resource_mut::<FloorState>();
+    let depth = floor_state.depth + 1;
+    floor_state.reset();
+    floor_state.depth = depth;
+    world.resource_mut::<TileWorld>().clear();
+    if let Ok(mut player_xform) = world
+        .query_filtered::<&mut Transform, With<Player>>()
+        .get_single_mut(world)
+    {
+        player_xform.translation.x = 0.;
+        player_xform.translation.y = 0.;
+    }
+    Ok(format!("descended to floor {}", depth + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{health::DamageEvent, simulation::Simulation},
+        std::fs,
+    };
+
+    #[test]
+    fn floor_layout_is_seeded_and_connected() {
+        for seed in 0..32 {
+            let layout = FloorLayout::generate(seed);
+            assert_eq!(layout.rooms, FloorLayout::generate(seed).rooms);
+            assert_eq!(layout.rooms.len(), ROOM_COUNT);
+            assert!(layout.rooms[&IVec2::ZERO].iter().all(|dir| dir.y == 0));
+
+            for (&room, doorways) in layout.rooms.iter() {
+                for &dir in doorways {
+                    assert!(layout.rooms[&(room + dir)].contains(&-dir));
+                }
+            }
+            let mut reached = HashSet::from_iter([IVec2::ZERO]);
+            let mut stack = vec![IVec2::ZERO];
+            while let Some(room) = stack.pop() {
+                stack.extend(layout.neighbours(room).filter(|&next| reached.insert(next)));
+            }
+            assert_eq!(reached.len(), ROOM_COUNT);
+        }
+    }
+
+    #[test]
+    fn rooms_generate_around_the_player_and_keep_their_state() {
+        let mut sim = Simulation::new();
+        let defs = ron::de::from_str::<EnemyDefs>(
+            &fs::read_to_string("assets/data/base.enemies.ron").unwrap(),
+        )
+        .unwrap();
+        let defs_handle = sim.app.world.resource_mut::<Assets<EnemyDefs>>().add(defs);
+        sim.app.world.insert_resource(EnemyDefsHandle(defs_handle));
+        sim.settle(256);
+        let layout_rooms = |sim: &Simulation, room: IVec2| {
+            let layout = sim.app.world.resource::<FloorLayout>();
+            layout
+                .neighbours(room)
+                .chain([room])
+                .collect::<HashSet<_>>()
+        };
+        let has_tiles = |sim: &Simulation, room: IVec2| {
+            sim.app
+                .world
+                .resource::<TileWorld>()
+                .tiles_in(room_tile_origin(room), TILEMAP_SIZE)
+                .next()
+                .is_some()
+        };
+        let enemies_in = |sim: &mut Simulation, room: IVec2| {
+            sim.app
+                .world
+                .query_filtered::<(Entity, &Transform), With<Enemy>>()
+                .iter(&sim.app.world)
+                .filter(|(_, xform)| room_at(xform.translation.truncate()) == room)
+                .map(|(enemy_id, _)| enemy_id)
+                .collect::<Vec<_>>()
+        };
+        let generated_rooms =
+            |sim: &Simulation| sim.app.world.resource::<FloorState>().generated.clone();
+        assert_eq!(generated_rooms(&sim), layout_rooms(&sim, IVec2::ZERO));
+        assert!(has_tiles(&sim, IVec2::ZERO));
+        assert!(enemies_in(&mut sim, IVec2::ZERO).is_empty());
+        assert!(!sim.app.world.resource::<FloorState>().rooms[&IVec2::ZERO].cleared);
+
+        let next = sim.app.world.resource::<FloorLayout>().rooms[&IVec2::ZERO][0];
+        let teleport = |sim: &mut Simulation, pos: Vec2| {
+            let mut player_xform = sim
+                .app
+                .world
+                .query_filtered::<&mut Transform, With<Player>>()
+                .single_mut(&mut sim.app.world);
+            player_xform.translation = pos.extend(player_xform.translation.z);
+        };
+        teleport(&mut sim, room_origin(next));
+        sim.step_n(2, &[]);
+
+        assert_eq!(sim.app.world.resource::<FloorState>().current, next);
+        assert_eq!(generated_rooms(&sim), layout_rooms(&sim, next));
+        assert!(layout_rooms(&sim, next)
+            .iter()
+            .all(|&room| has_tiles(&sim, room)));
+        assert!(layout_rooms(&sim, IVec2::ZERO)
+            .difference(&layout_rooms(&sim, next))
+            .all(|&room| !has_tiles(&sim, room)));
+        let enemies = enemies_in(&mut sim, next);
+        assert!(!enemies.is_empty());
+        assert_eq!(
+            sim.app.world.resource::<FloorState>().rooms[&next],
+            RoomState {
+                visited: true,
+                cleared: false,
+                looted: false,
+                hostile: true,
+            }
+        );
+
+        for enemy_id in enemies {
+            sim.app.world.send_event(DamageEvent {
+                target: enemy_id,
+                amount: 99,
+                knockback: Vec2::ZERO,
+            });
+        }
+        sim.step_n(2, &[]);
+        assert_eq!(
+            sim.app.world.resource::<FloorState>().rooms[&next],
+            RoomState {
+                visited: true,
+                cleared: true,
+                looted: true,
+                hostile: true,
+            }
+        );
+
+        teleport(&mut sim, Vec2::ZERO);
+        sim.step_n(2, &[]);
+        assert_eq!(sim.app.world.resource::<FloorState>().current, IVec2::ZERO);
+        assert!(sim.app.world.resource::<FloorState>().rooms[&next].looted);
+        assert_eq!(generated_rooms(&sim), layout_rooms(&sim, IVec2::ZERO));
+    }
+
+    #[test]
+    fn unloaded_rooms_keep_their_edits() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let start = sim.player::<Transform>().translation.truncate();
+        let floor_tile = tile::tile_at(start) - IVec2::Y;
+        assert!(sim
+            .app
+            .world
+            .resource::<TileWorld>()
+            .get(floor_tile)
+            .is_some());
+        sim.app.world.resource_mut::<TileWorld>().remove(floor_tile);
+
+        let layout = sim.app.world.resource::<FloorLayout>();
+        let near = layout
+            .neighbours(IVec2::ZERO)
+            .chain([IVec2::ZERO])
+            .collect::<HashSet<_>>();
+        let far = *layout
+            .rooms
+            .keys()
+            .find(|room| !near.contains(room))
+            .unwrap();
+        let teleport = |sim: &mut Simulation, pos: Vec2| {
+            let mut player_xform = sim
+                .app
+                .world
+                .query_filtered::<&mut Transform, With<Player>>()
+                .single_mut(&mut sim.app.world);
+            player_xform.translation = pos.extend(player_xform.translation.z);
+        };
+        teleport(&mut sim, room_origin(far));
+        sim.step_n(2, &[]);
+        let floor_state = sim.app.world.resource::<FloorState>();
+        assert!(!floor_state.generated.contains(&IVec2::ZERO));
+        assert!(floor_state.snapshots.contains_key(&IVec2::ZERO));
+        assert!(sim
+            .app
+            .world
+            .resource::<TileWorld>()
+            .tiles_in(room_tile_origin(IVec2::ZERO), TILEMAP_SIZE)
+            .next()
+            .is_none());
+
+        teleport(&mut sim, start);
+        sim.step_n(2, &[]);
+        let tile_world = sim.app.world.resource::<TileWorld>();
+        assert!(tile_world.get(floor_tile).is_none());
+        assert!(tile_world.get(floor_tile + IVec2::X).is_some());
+    }
+}
@@ -1,7 +1,41 @@
-use bevy::prelude::*;
+use {
+    bevy::prelude::*,
+    serde::{Deserialize, Serialize},
+    std::str::FromStr,
+};
 
-#[derive(States, Default, Debug, Hash, Eq, PartialEq, Clone)]
+#[derive(States, Default, Debug, Hash, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub enum GameState {
     #[default]
+    MainMenu,
+    Settings,
     Playing,
+    Paused,
+}
+
+pub const START_RUN: OnTransition<GameState> = OnTransition {
+    from: GameState::MainMenu,
+    to: GameState::Playing,
+};
+
+pub const END_RUN: OnEnter<GameState> = OnEnter(GameState::MainMenu);
+
+pub fn despawn_with<C: Component>(mut cmds: Commands, qry: Query<Entity, With<C>>) {
+    for id in qry.iter() {
+        cmds.entity(id).despawn_recursive();
+    }
+}
+
+impl FromStr for GameState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mainmenu" | "menu" => Ok(Self::MainMenu),
+            "settings" => Ok(Self::Settings),
+            "playing" => Ok(Self::Playing),
+            "paused" => Ok(Self::Paused),
+            _ => Err(format!("unknown game state `{s}`")),
+        }
+    }
 }
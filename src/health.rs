@@ -0,0 +1,65 @@
+use {super::game_state::GameState, bevy::prelude::*, bevy_rapier2d::prelude::*};
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_event::<Died>()
+            .add_systems(
+                FixedUpdate,
+                apply_damage.run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Self { current: max, max }
+    }
+}
+
+#[derive(Component)]
+pub struct Invulnerable;
+
+#[derive(Event)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: u32,
+    pub knockback: Vec2,
+}
+
+#[derive(Event)]
+pub struct Died {
+    pub entity: Entity,
+}
+
+fn apply_damage(
+    mut damage_evr: EventReader<DamageEvent>,
+    mut died_evw: EventWriter<Died>,
+    mut target_qry: Query<(&mut Health, Option<&mut Velocity>), Without<Invulnerable>>,
+) {
+    for damage in damage_evr.read() {
+        let Ok((mut health, vel)) = target_qry.get_mut(damage.target) else {
+            continue;
+        };
+        if health.current == 0 {
+            continue;
+        }
+        health.current = health.current.saturating_sub(damage.amount);
+        if health.current == 0 {
+            died_evw.send(Died {
+                entity: damage.target,
+            });
+        }
+        if let Some(mut vel) = vel {
+            vel.linvel += damage.knockback;
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use {
+    super::{
+        floor::FloorState,
+        game_state::{self, GameState},
+        health::Health,
+        inventory::{Inventory, ItemCatalog, ItemCatalogHandle},
+        player::Player,
+        weapon::Weapon,
+    },
+    bevy::prelude::*,
+};
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(game_state::START_RUN, spawn_hud)
+            .add_systems(game_state::END_RUN, game_state::despawn_with::<Hud>)
+            .add_systems(
+                Update,
+                (
+                    update_health,
+                    update_cooldown,
+                    update_inventory_text,
+                    update_floor_text,
+                )
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+const CURRENCY_ITEM: &str = "coin";
+const BAR_WIDTH: f32 = 80.;
+const BAR_HEIGHT: f32 = 6.;
+const FONT_SIZE: f32 = 14.;
+
+#[derive(Component)]
+pub struct Hud;
+
+#[derive(Component)]
+pub struct HealthFill;
+
+#[derive(Component)]
+pub struct HealthText;
+
+#[derive(Component)]
+pub struct CooldownFill;
+
+#[derive(Component)]
+pub struct WeaponText;
+
+#[derive(Component)]
+pub struct CurrencyText;
+
+#[derive(Component)]
+pub struct FloorText;
+
+fn bar(parent: &mut ChildBuilder, fill: impl Component, color: Color) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(BAR_WIDTH),
+                height: Val::Px(BAR_HEIGHT),
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.6).into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                fill,
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.),
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn text(parent: &mut ChildBuilder, marker: impl Component) {
+    parent.spawn((
+        marker,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: FONT_SIZE,
+                ..default()
+            },
+        ),
+    ));
+}
+
+fn spawn_hud(mut cmds: Commands) {
+    cmds.spawn((
+        Name::new("HUD"),
+        Hud,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.),
+                left: Val::Px(8.),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.),
+                ..default()
+            },
+            ..default()
+        },
+    ))
+    .with_children(|parent| {
+        bar(parent, HealthFill, Color::rgb(0.8, 0.15, 0.15));
+        text(parent, HealthText);
+        bar(parent, CooldownFill, Color::rgb(0.9, 0.9, 0.9));
+        text(parent, WeaponText);
+        text(parent, CurrencyText);
+        text(parent, FloorText);
+    });
+}
+
+fn update_health(
+    player_qry: Query<&Health, (With<Player>, Changed<Health>)>,
+    mut fill_qry: Query<&mut Style, With<HealthFill>>,
+    mut text_qry: Query<&mut Text, With<HealthText>>,
+) {
+    let Ok(player_health) = player_qry.get_single() else {
+        return;
+    };
+    let percent = player_health.current as f32 / player_health.max.max(1) as f32 * 100.;
+    for mut fill_style in fill_qry.iter_mut() {
+        fill_style.width = Val::Percent(percent);
+    }
+    for mut text in text_qry.iter_mut() {
+        text.sections[0].value = format!("{}/{}", player_health.current, player_health.max);
+    }
+}
+
+fn update_cooldown(
+    weapon_qry: Query<(&Weapon, &Parent), Changed<Weapon>>,
+    player_qry: Query<(), With<Player>>,
+    mut fill_qry: Query<&mut Style, With<CooldownFill>>,
+) {
+    for (weapon, parent) in weapon_qry.iter() {
+        if player_qry.get(parent.get()).is_err() {
+            continue;
+        }
+        let width = Val::Percent((1. - weapon.cooldown()) * 100.);
+        for mut fill_style in fill_qry.iter_mut() {
+            if fill_style.width != width {
+                fill_style.width = width;
+            }
+        }
+    }
+}
+
+fn update_inventory_text(
+    catalog_handle: Res<ItemCatalogHandle>,
+    catalogs: Res<Assets<ItemCatalog>>,
+    player_qry: Query<Ref<Inventory>, With<Player>>,
+    mut weapon_text_qry: Query<&mut Text, (With<WeaponText>, Without<CurrencyText>)>,
+    mut currency_text_qry: Query<&mut Text, With<CurrencyText>>,
+) {
+    let Ok(inventory) = player_qry.get_single() else {
+        return;
+    };
+    if !inventory.is_changed() && !catalogs.is_changed() {
+        return;
+    }
+    let catalog = catalogs.get(&catalog_handle.0);
+    let weapon = inventory.equipped_item().map_or("-", |stack| {
+        catalog
+            .and_then(|catalog| catalog.get(&stack.item))
+            .map_or(stack.item.as_str(), |item_def| item_def.name.as_str())
+    });
+
+    for mut text in weapon_text_qry.iter_mut() {
+        text.sections[0].value = String::from(weapon);
+    }
+    for mut text in currency_text_qry.iter_mut() {
+        text.sections[0].value = format!("{} coins", inventory.count(CURRENCY_ITEM));
+    }
+}
+
+fn update_floor_text(
+    floor_state: Res<FloorState>,
+    mut text_qry: Query<&mut Text, With<FloorText>>,
+) {
+    if !floor_state.is_changed() {
+        return;
+    }
+    for mut text in text_qry.iter_mut() {
+        let value = format!("Floor {}", floor_state.depth + 1);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            console::Console, health::DamageEvent, inventory::ItemStack, simulation::Simulation,
+        },
+    };
+
+    fn text<T: Component>(sim: &mut Simulation) -> String {
+        sim.app
+            .world
+            .query_filtered::<&Text, With<T>>()
+            .single(&sim.app.world)
+            .sections[0]
+            .value
+            .clone()
+    }
+
+    #[test]
+    fn hud_follows_player_state() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        assert_eq!(text::<HealthText>(&mut sim), "5/5");
+        assert_eq!(text::<CurrencyText>(&mut sim), "0 coins");
+
+        let player_id = sim
+            .app
+            .world
+            .query_filtered::<Entity, With<Player>>()
+            .single(&sim.app.world);
+        sim.app.world.send_event(DamageEvent {
+            target: player_id,
+            amount: 2,
+            knockback: Vec2::ZERO,
+        });
+        sim.app
+            .world
+            .get_mut::<Inventory>(player_id)
+            .unwrap()
+            .add(ItemStack::new(CURRENCY_ITEM, 12), 999);
+        sim.step_n(2, &[]);
+
+        assert_eq!(text::<HealthText>(&mut sim), "3/5");
+        assert_eq!(
+            sim.app
+                .world
+                .query_filtered::<&Style, With<HealthFill>>()
+                .single(&sim.app.world)
+                .width,
+            Val::Percent(60.)
+        );
+        assert_eq!(text::<CurrencyText>(&mut sim), "12 coins");
+
+        assert_eq!(text::<FloorText>(&mut sim), "Floor 1");
+        sim.app.world.resource_mut::<Console>().run("descend");
+        sim.step_n(2, &[]);
+        assert_eq!(text::<FloorText>(&mut sim), "Floor 2");
+    }
+}
@@ -0,0 +1,380 @@
+use {
+    super::{
+        game_state::{self, GameState},
+        physics::{Acceleration, Grounded, NetDirection, TerminalVelocity, ITEM_GROUP, TILE_GROUP},
+        player::{self, Player, PlayerAction, TickActions},
+        ron_asset::{self, RonAssetPlugin},
+        sprite_flip::Flippable,
+        stats::{ModifierSource, StatModifier, Stats},
+        weapon::Weapon,
+    },
+    bevy::{prelude::*, sprite::Anchor, utils::HashMap},
+    bevy_rapier2d::prelude::*,
+    serde::{Deserialize, Serialize},
+};
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<ItemCatalog>::new(&["items.ron"]))
+            .add_systems(Startup, load_item_catalog)
+            .add_systems(game_state::START_RUN, spawn_level_pickups)
+            .add_systems(game_state::END_RUN, game_state::despawn_with::<Pickup>)
+            .add_systems(
+                FixedUpdate,
+                cycle_equipment
+                    .after(player::latch_player_actions)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    collect_pickups,
+                    attach_pickup_sprites,
+                    sync_equipped_weapons,
+                    apply_equipment_modifiers,
+                )
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ItemDef {
+    pub name: String,
+    pub icon: String,
+    #[serde(default = "ron_asset::one")]
+    pub max_stack: u32,
+    #[serde(default)]
+    pub weapon: Option<String>,
+    #[serde(default)]
+    pub modifiers: Vec<StatModifier>,
+}
+
+#[derive(Asset, TypePath, Deserialize, Deref)]
+#[serde(transparent)]
+pub struct ItemCatalog(pub HashMap<String, ItemDef>);
+
+#[derive(Resource, Deref)]
+pub struct ItemCatalogHandle(pub Handle<ItemCatalog>);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ItemStack {
+    pub item: String,
+    pub count: u32,
+}
+
+impl ItemStack {
+    pub fn new(item: impl Into<String>, count: u32) -> Self {
+        Self {
+            item: item.into(),
+            count,
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct Inventory {
+    pub slots: Vec<Option<ItemStack>>,
+    pub equipped: Option<usize>,
+}
+
+impl Inventory {
+    pub fn new(slot_count: usize) -> Self {
+        Self {
+            slots: vec![None; slot_count],
+            equipped: None,
+        }
+    }
+
+    pub fn add(&mut self, mut stack: ItemStack, max_stack: u32) -> Option<ItemStack> {
+        for slot in self
+            .slots
+            .iter_mut()
+            .flatten()
+            .filter(|slot| slot.item == stack.item)
+        {
+            let moved = max_stack.saturating_sub(slot.count).min(stack.count);
+            slot.count += moved;
+            stack.count -= moved;
+            if stack.count == 0 {
+                return None;
+            }
+        }
+        for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+            let moved = stack.count.min(max_stack);
+            *slot = Some(ItemStack::new(stack.item.clone(), moved));
+            stack.count -= moved;
+            if stack.count == 0 {
+                return None;
+            }
+        }
+        Some(stack)
+    }
+
+    pub fn count(&self, item: &str) -> u32 {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|slot| slot.item == item)
+            .map(|slot| slot.count)
+            .sum()
+    }
+
+    pub fn take(&mut self, item: &str, mut count: u32) -> bool {
+        if self.count(item) < count {
+            return false;
+        }
+        for slot in self.slots.iter_mut() {
+            let Some(stack) = slot.as_mut().filter(|stack| stack.item == item) else {
+                continue;
+            };
+            let taken = stack.count.min(count);
+            stack.count -= taken;
+            count -= taken;
+            if stack.count == 0 {
+                *slot = None;
+            }
+            if count == 0 {
+                break;
+            }
+        }
+        true
+    }
+
+    pub fn equipped_item(&self) -> Option<&ItemStack> {
+        self.slots.get(self.equipped?)?.as_ref()
+    }
+}
+
+#[derive(Component, Deref, DerefMut)]
+pub struct Pickup(pub ItemStack);
+
+#[derive(Component, Default)]
+pub struct EquippedWeapon {
+    item: Option<String>,
+}
+
+fn load_item_catalog(mut cmds: Commands, asset_server: Res<AssetServer>) {
+    cmds.insert_resource(ItemCatalogHandle(asset_server.load("data/base.items.ron")));
+}
+
+pub fn spawn_pickup(cmds: &mut Commands, stack: ItemStack, pos: Vec2, vel: Vec2) -> Entity {
+    cmds.spawn((
+        Name::new(format!("Pickup ({})", stack.item)),
+        Pickup(stack),
+        SpriteBundle {
+            transform: Transform::from_translation(pos.extend(1.)),
+            ..default()
+        },
+        Collider::ball(4.),
+        Sensor,
+        CollisionGroups::new(ITEM_GROUP, Group::ALL),
+        KinematicCharacterController {
+            filter_groups: Some(CollisionGroups::new(ITEM_GROUP, TILE_GROUP)),
+            ..default()
+        },
+        Friction::coefficient(3.),
+        Velocity::linear(vel),
+        TerminalVelocity(Vec2::new(100., 200.)),
+        Acceleration(Vec2::new(0., 500.)),
+        NetDirection { x: 0, y: -1 },
+        Grounded::default(),
+    ))
+    .id()
+}
+
+fn spawn_level_pickups(mut cmds: Commands) {
+    for x in [-48., -32., 32., 48.] {
+        spawn_pickup(
+            &mut cmds,
+            ItemStack::new("coin", 1),
+            Vec2::new(x, -230.),
+            Vec2::ZERO,
+        );
+    }
+    for (item, x) in [("spear", -80.), ("hammer", 80.), ("bow", 112.)] {
+        spawn_pickup(
+            &mut cmds,
+            ItemStack::new(item, 1),
+            Vec2::new(x, -230.),
+            Vec2::ZERO,
+        );
+    }
+}
+
+fn attach_pickup_sprites(
+    catalog_handle: Res<ItemCatalogHandle>,
+    catalogs: Res<Assets<ItemCatalog>>,
+    asset_server: Res<AssetServer>,
+    mut pickup_qry: Query<(&Pickup, &mut Handle<Image>)>,
+) {
+    let Some(catalog) = catalogs.get(&catalog_handle.0) else {
+        return;
+    };
+    for (pickup, mut tex) in pickup_qry.iter_mut() {
+        if *tex != Handle::default() {
+            continue;
+        }
+        if let Some(item_def) = catalog.get(&pickup.item) {
+            *tex = asset_server.load(&item_def.icon);
+        }
+    }
+}
+
+fn collect_pickups(
+    mut cmds: Commands,
+    rapier_ctx: Res<RapierContext>,
+    catalog_handle: Res<ItemCatalogHandle>,
+    catalogs: Res<Assets<ItemCatalog>>,
+    mut collector_qry: Query<(&mut Inventory, &GlobalTransform, &Collider)>,
+    mut pickup_qry: Query<&mut Pickup>,
+) {
+    let Some(catalog) = catalogs.get(&catalog_handle.0) else {
+        return;
+    };
+    for (mut inventory, glob_xform, collider) in collector_qry.iter_mut() {
+        rapier_ctx.intersections_with_shape(
+            glob_xform.translation().truncate(),
+            0.,
+            collider,
+            QueryFilter::new().exclude_solids(),
+            |pickup_id| {
+                let Ok(mut pickup) = pickup_qry.get_mut(pickup_id) else {
+                    return true;
+                };
+                let Some(item_def) = catalog.get(&pickup.item) else {
+                    return true;
+                };
+                match inventory.add(pickup.0.clone(), item_def.max_stack) {
+                    Some(leftover) => pickup.0 = leftover,
+                    None => cmds.entity(pickup_id).despawn_recursive(),
+                }
+                true
+            },
+        );
+    }
+}
+
+fn cycle_equipment(
+    catalog_handle: Res<ItemCatalogHandle>,
+    catalogs: Res<Assets<ItemCatalog>>,
+    mut player_qry: Query<(&mut Inventory, &TickActions), With<Player>>,
+) {
+    let Some(catalog) = catalogs.get(&catalog_handle.0) else {
+        return;
+    };
+    for (mut inventory, player_tick_actions) in player_qry.iter_mut() {
+        if !player_tick_actions.just_pressed(PlayerAction::CycleEquipment) {
+            continue;
+        }
+        let start = inventory.equipped.map_or(0, |equipped| equipped + 1);
+        inventory.equipped = (start..inventory.slots.len()).find(|&slot| {
+            inventory.slots[slot]
+                .as_ref()
+                .and_then(|stack| catalog.get(&stack.item))
+                .is_some_and(|item_def| item_def.weapon.is_some())
+        });
+    }
+}
+
+fn sync_equipped_weapons(
+    mut cmds: Commands,
+    asset_server: Res<AssetServer>,
+    catalog_handle: Res<ItemCatalogHandle>,
+    catalogs: Res<Assets<ItemCatalog>>,
+    mut owner_qry: Query<(Entity, &Inventory, &mut EquippedWeapon, Option<&Children>)>,
+    weapon_qry: Query<(), With<Weapon>>,
+) {
+    let Some(catalog) = catalogs.get(&catalog_handle.0) else {
+        return;
+    };
+    for (owner_id, inventory, mut equipped_weapon, children) in owner_qry.iter_mut() {
+        let equipped_item = inventory.equipped_item().map(|stack| &stack.item);
+        if equipped_weapon.item.as_ref() == equipped_item {
+            continue;
+        }
+        equipped_weapon.item = equipped_item.cloned();
+
+        if let Some(children) = children {
+            for &child_id in children.iter() {
+                if weapon_qry.contains(child_id) {
+                    cmds.entity(child_id).despawn_recursive();
+                }
+            }
+        }
+        let Some((item_def, weapon_def)) = equipped_item
+            .and_then(|item| catalog.get(item))
+            .and_then(|item_def| Some((item_def, item_def.weapon.as_ref()?)))
+        else {
+            continue;
+        };
+        cmds.entity(owner_id).with_children(|parent| {
+            parent.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        anchor: Anchor::BottomCenter,
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0., 0., -1.),
+                    ..default()
+                },
+                Flippable::default(),
+                Weapon::new(asset_server.load(weapon_def)),
+                Name::new(item_def.name.clone()),
+            ));
+        });
+    }
+}
+
+fn apply_equipment_modifiers(
+    catalog_handle: Res<ItemCatalogHandle>,
+    catalogs: Res<Assets<ItemCatalog>>,
+    mut owner_qry: Query<(&Inventory, &mut Stats), Changed<Inventory>>,
+) {
+    let Some(catalog) = catalogs.get(&catalog_handle.0) else {
+        return;
+    };
+    for (inventory, mut stats) in owner_qry.iter_mut() {
+        let modifiers = inventory
+            .equipped_item()
+            .and_then(|stack| catalog.get(&stack.item))
+            .map(|item_def| item_def.modifiers.clone())
+            .unwrap_or_default();
+        if stats.modifiers(ModifierSource::Equipment) != modifiers.as_slice() {
+            stats.set_modifiers(ModifierSource::Equipment, modifiers);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_fills_existing_stacks_before_empty_slots() {
+        let mut inventory = Inventory::new(3);
+        assert_eq!(inventory.add(ItemStack::new("coin", 7), 10), None);
+        assert_eq!(inventory.add(ItemStack::new("sword", 1), 1), None);
+        assert_eq!(inventory.add(ItemStack::new("coin", 5), 10), None);
+
+        assert_eq!(
+            inventory.slots,
+            vec![
+                Some(ItemStack::new("coin", 10)),
+                Some(ItemStack::new("sword", 1)),
+                Some(ItemStack::new("coin", 2)),
+            ]
+        );
+        assert_eq!(
+            inventory.add(ItemStack::new("coin", 20), 10),
+            Some(ItemStack::new("coin", 12))
+        );
+
+        assert!(!inventory.take("coin", 21));
+        assert!(inventory.take("coin", 12));
+        assert_eq!(inventory.count("coin"), 8);
+        assert_eq!(inventory.slots[2], None);
+    }
+}
@@ -0,0 +1,317 @@
+use {
+    super::{
+        console::AddConsoleCommand,
+        floor::{self, FloorState},
+        game_state::{self, GameState},
+        player::Player,
+        tile::{self, TileChanged, TileWorld, TILEMAP_SIZE, TILE_SIZE},
+    },
+    bevy::{
+        prelude::*,
+        render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+        transform::TransformSystem,
+        utils::{HashMap, HashSet},
+    },
+    serde::Deserialize,
+};
+
+pub struct LightingPlugin;
+
+impl Plugin for LightingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LightGrid>()
+            .add_systems(Startup, spawn_fog)
+            .add_systems(game_state::END_RUN, reset_light_grid)
+            .add_systems(
+                PostUpdate,
+                (update_light_grid, draw_fog)
+                    .chain()
+                    .after(tile::sync_chunks)
+                    .after(TransformSystem::TransformPropagate)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_console_command("torch", "torch: place a torch at the player", torch);
+    }
+}
+
+const MEMORY_LIGHT: f32 = 0.2;
+const FOG_ROOMS: i32 = 3;
+const FOG_Z: f32 = 10.;
+
+#[derive(Component)]
+pub struct Fog;
+
+#[derive(Component, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct LightSource {
+    pub radius: f32,
+    pub intensity: f32,
+}
+
+impl LightSource {
+    pub const LANTERN: Self = Self {
+        radius: 8.,
+        intensity: 1.,
+    };
+    const TORCH: Self = Self {
+        radius: 6.,
+        intensity: 0.9,
+    };
+}
+
+#[derive(Resource, Default)]
+pub struct LightGrid {
+    sources: Vec<(IVec2, LightSource)>,
+    light: HashMap<IVec2, f32>,
+    occluders: HashSet<IVec2>,
+}
+
+impl LightGrid {
+    pub fn level(&self, tile: IVec2) -> f32 {
+        self.light.get(&tile).copied().unwrap_or_default()
+    }
+}
+
+fn fog_size() -> IVec2 {
+    IVec2::new(TILEMAP_SIZE.x as i32, TILEMAP_SIZE.y as i32) * FOG_ROOMS
+}
+
+fn fog_min(room: IVec2) -> IVec2 {
+    floor::room_tile_origin(room) - IVec2::new(TILEMAP_SIZE.x as i32, TILEMAP_SIZE.y as i32)
+}
+
+pub fn line_of_sight(tile_world: &TileWorld, from: IVec2, to: IVec2) -> bool {
+    let delta = (to - from).abs();
+    let step = (to - from).signum();
+    let mut err = delta.x - delta.y;
+    let mut tile = from;
+
+    while tile != to {
+        let err2 = err * 2;
+        if err2 > -delta.y {
+            err -= delta.y;
+            tile.x += step.x;
+        }
+        if err2 < delta.x {
+            err += delta.x;
+            tile.y += step.y;
+        }
+        if tile != to && tile_world.get(tile).is_some() {
+            return false;
+        }
+    }
+    true
+}
+
+pub fn compute_light(
+    tile_world: &TileWorld,
+    sources: &[(IVec2, LightSource)],
+) -> HashMap<IVec2, f32> {
+    let mut light = HashMap::new();
+    for &(origin, source) in sources {
+        let reach = source.radius.ceil() as i32;
+        for y in -reach..=reach {
+            for x in -reach..=reach {
+                let offset = IVec2::new(x, y);
+                let dist = offset.as_vec2().length();
+                if dist > source.radius || !line_of_sight(tile_world, origin, origin + offset) {
+                    continue;
+                }
+                let level = (source.intensity * (1. - dist / source.radius)).clamp(0., 1.);
+                let tile_light = light.entry(origin + offset).or_insert(0_f32);
+                *tile_light = tile_light.max(level);
+            }
+        }
+    }
+    light
+}
+
+pub fn update_light_grid(
+    tile_world: Res<TileWorld>,
+    mut light_grid: ResMut<LightGrid>,
+    mut floor_state: ResMut<FloorState>,
+    mut tile_changed_evr: EventReader<TileChanged>,
+    source_qry: Query<(&GlobalTransform, &LightSource)>,
+) {
+    let sources = source_qry
+        .iter()
+        .map(|(glob_xform, &source)| (tile::tile_at(glob_xform.translation().truncate()), source))
+        .collect::<Vec<_>>();
+    let occlusion_changed = tile_changed_evr.read().any(|tile_changed| {
+        light_grid.light.contains_key(&tile_changed.tile)
+            && light_grid.occluders.contains(&tile_changed.tile) != tile_changed.solid
+    });
+    if sources == light_grid.sources && !occlusion_changed {
+        return;
+    }
+
+    light_grid.light = compute_light(&tile_world, &sources);
+    light_grid.occluders = light_grid
+        .light
+        .keys()
+        .copied()
+        .filter(|&tile| tile_world.get(tile).is_some())
+        .collect();
+    light_grid.sources = sources;
+    let newly_lit = light_grid
+        .light
+        .iter()
+        .filter(|&(tile, &level)| level > 0. && !floor_state.explored.contains(tile))
+        .map(|(&tile, _)| tile)
+        .collect::<Vec<_>>();
+    if !newly_lit.is_empty() {
+        floor_state.explored.extend(newly_lit);
+    }
+}
+
+fn spawn_fog(mut cmds: Commands, mut images: ResMut<Assets<Image>>) {
+    let size = fog_size();
+    let fog = Image::new_fill(
+        Extent3d {
+            width: size.x as u32,
+            height: size.y as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    cmds.spawn((
+        Name::new("Fog"),
+        Fog,
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(size.as_vec2() * Vec2::from(TILE_SIZE)),
+                ..default()
+            },
+            texture: images.add(fog),
+            transform: Transform::from_xyz(0., 0., FOG_Z),
+            ..default()
+        },
+    ));
+}
+
+fn reset_light_grid(mut light_grid: ResMut<LightGrid>) {
+    *light_grid = LightGrid::default();
+}
+
+fn draw_fog(
+    light_grid: Res<LightGrid>,
+    floor_state: Res<FloorState>,
+    mut images: ResMut<Assets<Image>>,
+    mut fog_qry: Query<(&Handle<Image>, &mut Transform), With<Fog>>,
+) {
+    if !light_grid.is_changed() && !floor_state.is_changed() {
+        return;
+    }
+    let Ok((fog_handle, mut fog_xform)) = fog_qry.get_single_mut() else {
+        return;
+    };
+    let Some(fog) = images.get_mut(fog_handle) else {
+        return;
+    };
+    let size = fog_size();
+    let min = fog_min(floor_state.current);
+    for (i, pixel) in fog.data.chunks_exact_mut(4).enumerate() {
+        let tile = min + IVec2::new(i as i32 % size.x, size.y - 1 - i as i32 / size.x);
+        let level = match light_grid.level(tile) {
+            level if level > 0. => level.max(MEMORY_LIGHT),
+            _ if floor_state.explored.contains(&tile) => MEMORY_LIGHT,
+            _ => 0.,
+        };
+        pixel[3] = ((1. - level) * 255.).round() as u8;
+    }
+    fog_xform.translation = floor::room_origin(floor_state.current).extend(FOG_Z);
+}
+
+fn torch(world: &mut World, _: &[&str]) -> Result<String, String> {
+    let Some(player_xform) = world
+        .query_filtered::<&Transform, With<Player>>()
+        .iter(world)
+        .next()
+        .copied()
+    else {
+        return Err(String::from("no player"));
+    };
+    world.spawn((
+        Name::new("Torch"),
+        LightSource::TORCH,
+        TransformBundle::from_transform(Transform::from_translation(player_xform.translation)),
+    ));
+    Ok(String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::simulation::Simulation};
+
+    fn fog_alpha(sim: &mut Simulation, tile: IVec2) -> u8 {
+        let fog_handle = sim
+            .app
+            .world
+            .query_filtered::<&Handle<Image>, With<Fog>>()
+            .single(&sim.app.world)
+            .clone();
+        let fog = sim
+            .app
+            .world
+            .resource::<Assets<Image>>()
+            .get(&fog_handle)
+            .unwrap();
+        let size = fog_size();
+        let local = tile - fog_min(sim.app.world.resource::<FloorState>().current);
+        fog.data[((size.y - 1 - local.y) * size.x + local.x) as usize * 4 + 3]
+    }
+
+    #[test]
+    fn light_falls_off_and_is_occluded_by_solid_tiles() {
+        let mut tile_world = TileWorld::default();
+        for y in -2..=2 {
+            tile_world.set(IVec2::new(3, y), TileTextureIndex(0));
+        }
+        let light = compute_light(&tile_world, &[(IVec2::ZERO, LightSource::LANTERN)]);
+        let level = |tile: IVec2| light.get(&tile).copied().unwrap_or_default();
+
+        assert_eq!(level(IVec2::ZERO), 1.);
+        assert!(level(IVec2::new(1, 0)) > level(IVec2::new(2, 0)));
+        assert!(level(IVec2::new(3, 0)) > 0.);
+        assert_eq!(level(IVec2::new(4, 0)), 0.);
+        assert_eq!(level(IVec2::new(6, 1)), 0.);
+        assert!(level(IVec2::new(-4, 0)) > 0.);
+        assert_eq!(level(IVec2::new(-9, 0)), 0.);
+    }
+
+    #[test]
+    fn explored_tiles_are_remembered_after_the_light_moves_away() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let player_tile = tile::tile_at(sim.player::<Transform>().translation.truncate());
+        assert!(sim.app.world.resource::<LightGrid>().level(player_tile) > 0.);
+        assert!(sim
+            .app
+            .world
+            .resource::<FloorState>()
+            .explored
+            .contains(&player_tile));
+        assert_eq!(fog_alpha(&mut sim, player_tile), 0);
+        assert_eq!(fog_alpha(&mut sim, player_tile + IVec2::new(0, 28)), 255);
+
+        let mut player_xform = sim
+            .app
+            .world
+            .query_filtered::<&mut Transform, With<Player>>()
+            .single_mut(&mut sim.app.world);
+        player_xform.translation.x += 20. * tile::TILE_SIZE.x;
+        sim.step_n(2, &[]);
+        assert_eq!(sim.app.world.resource::<LightGrid>().level(player_tile), 0.);
+        assert!(sim
+            .app
+            .world
+            .resource::<FloorState>()
+            .explored
+            .contains(&player_tile));
+        assert_eq!(
+            fog_alpha(&mut sim, player_tile),
+            255 - (MEMORY_LIGHT * 255.).round() as u8
+        );
+    }
+}
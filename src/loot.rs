@@ -0,0 +1,278 @@
+use {
+    super::{
+        console::AddConsoleCommand,
+        game_state::GameState,
+        inventory::{self, ItemStack},
+        player::Player,
+        rng::{RunSeed, SeededRng},
+        ron_asset::{self, RonAssetPlugin},
+    },
+    bevy::{prelude::*, utils::HashMap},
+    serde::Deserialize,
+};
+
+pub struct LootPlugin;
+
+impl Plugin for LootPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<LootTables>::new(&["loot.ron"]))
+            .add_event::<DropLoot>()
+            .insert_resource(LootRng(SeededRng::new(LOOT_STREAM)))
+            .add_systems(Startup, load_loot_tables)
+            .add_systems(
+                Update,
+                (
+                    seed_loot_rng.run_if(resource_changed::<RunSeed>()),
+                    drop_loot,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_console_command("loot", "loot <table>: drop loot at the player", loot);
+    }
+}
+
+const MAX_TABLE_DEPTH: u32 = 8;
+const LOOT_STREAM: u64 = 0x6c6f_6f74;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rarity {
+    #[default]
+    Common,
+    Uncommon,
+    Rare,
+    Epic,
+    Legendary,
+}
+
+impl Rarity {
+    pub fn weight(self) -> u32 {
+        match self {
+            Self::Common => 60,
+            Self::Uncommon => 25,
+            Self::Rare => 10,
+            Self::Epic => 4,
+            Self::Legendary => 1,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum LootDrop {
+    Nothing,
+    Item {
+        item: String,
+        #[serde(default = "ron_asset::one")]
+        min: u32,
+        #[serde(default = "ron_asset::one")]
+        max: u32,
+    },
+    Table(String),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LootEntry {
+    #[serde(default)]
+    pub rarity: Rarity,
+    #[serde(default)]
+    pub weight: Option<u32>,
+    pub drop: LootDrop,
+}
+
+impl LootEntry {
+    pub fn weight(&self) -> u32 {
+        self.weight.unwrap_or(self.rarity.weight())
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LootTable {
+    #[serde(default)]
+    pub guaranteed: Vec<LootDrop>,
+    #[serde(default = "ron_asset::one")]
+    pub rolls: u32,
+    #[serde(default)]
+    pub entries: Vec<LootEntry>,
+}
+
+#[derive(Asset, TypePath, Deserialize, Deref)]
+#[serde(transparent)]
+pub struct LootTables(pub HashMap<String, LootTable>);
+
+impl LootTables {
+    pub fn roll(&self, table: &str, rng: &mut SeededRng) -> Vec<ItemStack> {
+        let mut drops = Vec::new();
+        self.roll_into(table, rng, 0, &mut drops);
+        drops
+    }
+
+    fn roll_into(&self, table: &str, rng: &mut SeededRng, depth: u32, drops: &mut Vec<ItemStack>) {
+        if depth > MAX_TABLE_DEPTH {
+            warn!("loot table {table} nested too deeply");
+            return;
+        }
+        let Some(loot_table) = self.get(table) else {
+            warn!("unknown loot table {table}");
+            return;
+        };
+
+        for drop in loot_table.guaranteed.iter() {
+            self.resolve(drop, rng, depth, drops);
+        }
+        let total_weight = loot_table
+            .entries
+            .iter()
+            .map(LootEntry::weight)
+            .sum::<u32>();
+        if total_weight == 0 {
+            return;
+        }
+        for _ in 0..loot_table.rolls {
+            let mut pick = rng.range(0..total_weight);
+            for entry in loot_table.entries.iter() {
+                if pick < entry.weight() {
+                    self.resolve(&entry.drop, rng, depth, drops);
+                    break;
+                }
+                pick -= entry.weight();
+            }
+        }
+    }
+
+    fn resolve(
+        &self,
+        drop: &LootDrop,
+        rng: &mut SeededRng,
+        depth: u32,
+        drops: &mut Vec<ItemStack>,
+    ) {
+        match drop {
+            LootDrop::Nothing => {}
+            LootDrop::Item { item, min, max } => {
+                let count = rng.range(*min..max.max(min) + 1);
+                if count > 0 {
+                    drops.push(ItemStack::new(item.clone(), count));
+                }
+            }
+            LootDrop::Table(table) => self.roll_into(table, rng, depth + 1, drops),
+        }
+    }
+}
+
+#[derive(Resource, Deref)]
+pub struct LootTablesHandle(pub Handle<LootTables>);
+
+#[derive(Resource, Deref, DerefMut)]
+pub struct LootRng(pub SeededRng);
+
+#[derive(Event)]
+pub struct DropLoot {
+    pub table: String,
+    pub pos: Vec2,
+}
+
+fn load_loot_tables(mut cmds: Commands, asset_server: Res<AssetServer>) {
+    cmds.insert_resource(LootTablesHandle(asset_server.load("data/base.loot.ron")));
+}
+
+fn seed_loot_rng(mut rng: ResMut<LootRng>, seed: Res<RunSeed>) {
+    rng.0 = SeededRng::new(seed.0 ^ LOOT_STREAM);
+}
+
+fn drop_loot(
+    mut cmds: Commands,
+    mut drop_loot_evr: EventReader<DropLoot>,
+    loot_tables_handle: Res<LootTablesHandle>,
+    loot_tables: Res<Assets<LootTables>>,
+    mut rng: ResMut<LootRng>,
+) {
+    let Some(loot_tables) = loot_tables.get(&loot_tables_handle.0) else {
+        return;
+    };
+    for drop_loot in drop_loot_evr.read() {
+        for stack in loot_tables.roll(&drop_loot.table, &mut rng) {
+            let vel = Vec2::new(rng.range(0..121) as f32 - 60., rng.range(120..201) as f32);
+            inventory::spawn_pickup(&mut cmds, stack, drop_loot.pos, vel);
+        }
+    }
+}
+
+fn loot(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let &[table] = args else {
+        return Err(String::from("usage: loot <table>"));
+    };
+    let player_xform = world
+        .query_filtered::<&Transform, With<Player>>()
+        .get_single(world)
+        .map_err(|_| String::from("no player"))?;
+
+    let pos = player_xform.translation.truncate() + Vec2::Y * 16.;
+    world.send_event(DropLoot {
+        table: String::from(table),
+        pos,
+    });
+    Ok(String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{physics::Grounded, simulation::Simulation},
+        bevy::ecs::system::RunSystemOnce,
+    };
+
+    const TABLES: &str = r#"{
+        "chest": (
+            guaranteed: [Item(item: "coin", min: 5, max: 10)],
+            rolls: 3,
+            entries: [
+                (rarity: Common, drop: Nothing),
+                (rarity: Rare, drop: Table("gems")),
+                (weight: Some(0), drop: Item(item: "sword")),
+            ],
+        ),
+        "gems": (
+            entries: [(drop: Item(item: "ruby"))],
+        ),
+    }"#;
+
+    #[test]
+    fn rolls_are_seeded_and_respect_tables() {
+        let loot_tables = ron::de::from_str::<LootTables>(TABLES).unwrap();
+
+        for seed in 0..64 {
+            let drops = loot_tables.roll("chest", &mut SeededRng::new(seed));
+            assert_eq!(drops, loot_tables.roll("chest", &mut SeededRng::new(seed)));
+            assert_eq!(drops[0].item, "coin");
+            assert!((5..=10).contains(&drops[0].count));
+            assert!(drops[1..]
+                .iter()
+                .all(|stack| stack == &ItemStack::new("ruby", 1)));
+            assert!(drops.len() <= 4);
+        }
+        assert!((0..64)
+            .map(|seed| loot_tables.roll("chest", &mut SeededRng::new(seed)))
+            .any(|drops| drops.len() > 1));
+    }
+
+    #[test]
+    fn dropped_pickups_settle_on_the_floor() {
+        let mut sim = Simulation::new();
+        let pickup_id = sim.app.world.run_system_once(|mut cmds: Commands| {
+            inventory::spawn_pickup(
+                &mut cmds,
+                ItemStack::new("coin", 1),
+                Vec2::new(-100., -200.),
+                Vec2::new(40., 150.),
+            )
+        });
+
+        sim.step_n(256, &[]);
+
+        assert!(sim.app.world.get::<Grounded>(pickup_id).unwrap().0);
+        let pickup_xform = sim.app.world.get::<Transform>(pickup_id).unwrap();
+        assert!((pickup_xform.translation.y + 236.).abs() < 1.);
+        assert!(pickup_xform.translation.x > -100.);
+    }
+}
@@ -1,66 +1,169 @@
+mod aim;
 mod animation;
+mod autotile;
+mod cli;
+mod console;
+#[cfg(feature = "dev")]
+mod dev_tools;
+mod door;
+mod enemy;
+mod floor;
 mod game_state;
+mod health;
+mod hud;
+mod inventory;
+mod lighting;
+mod loot;
 mod main_camera;
-mod mouse_position;
+mod menu;
+mod minimap;
 mod physics;
 mod player;
+mod projectile;
+mod replay;
+mod rng;
+mod ron_asset;
+mod save;
+mod settings;
+#[cfg(test)]
+mod simulation;
+mod sound;
 mod sprite_flip;
+mod stats;
+mod status_effect;
 mod tile;
+mod time_control;
+mod trap;
 mod weapon;
 
+#[cfg(all(feature = "dev", feature = "release"))]
+compile_error!("the `dev` and `release` features are mutually exclusive");
+
 use {
+    aim::AimPlugin,
     animation::AnimationPlugin,
+    autotile::AutotilePlugin,
     bevy::{
-        // diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+        audio::{AudioPlugin, SpatialScale},
         prelude::*,
-        window::{PresentMode, WindowMode, WindowResolution},
     },
     bevy_ecs_tilemap::prelude::*,
-    bevy_inspector_egui::quick::WorldInspectorPlugin,
     bevy_rapier2d::prelude::*,
+    door::DoorPlugin,
+    enemy::EnemyPlugin,
+    floor::FloorPlugin,
     game_state::GameState,
+    health::HealthPlugin,
+    hud::HudPlugin,
+    inventory::InventoryPlugin,
     leafwing_input_manager::prelude::*,
+    lighting::LightingPlugin,
+    loot::LootPlugin,
     // main_camera::MainCameraPlugin,
-    mouse_position::MousePositionPlugin,
-    physics::PhysicsPlugin,
+    menu::MenuPlugin,
+    minimap::MinimapPlugin,
+    physics::{PhysicsPlugin, TIMESTEP},
     player::{PlayerAction, PlayerPlugin},
+    projectile::ProjectilePlugin,
+    replay::ReplayPlugin,
+    rng::RngPlugin,
+    save::SavePlugin,
+    settings::{Settings, SettingsFile, SettingsPlugin},
+    sound::SoundPlugin,
     sprite_flip::SpriteFlipPlugin,
+    stats::StatsPlugin,
+    status_effect::StatusEffectPlugin,
     tile::TilePlugin,
+    time_control::TimeControlPlugin,
+    trap::TrapPlugin,
     weapon::WeaponPlugin,
 };
 
+#[cfg(feature = "release")]
+use bevy::log::{Level, LogPlugin};
+
 fn main() {
-    App::new()
-        .add_state::<GameState>()
+    let settings_file = SettingsFile::default();
+    let (settings, settings_err) = match settings_file.read() {
+        Ok(settings) => (settings, None),
+        Err(err) => (Settings::default(), Some(err)),
+    };
+    let default_plugins = DefaultPlugins
+        .set(ImagePlugin::default_nearest())
+        .set(AudioPlugin {
+            spatial_scale: SpatialScale::new_2d(sound::SPATIAL_SCALE),
+            ..default()
+        })
+        .set(WindowPlugin {
+            primary_window: Some(settings.video.window()),
+            ..default()
+        });
+    #[cfg(feature = "release")]
+    let default_plugins = default_plugins.set(LogPlugin {
+        level: Level::WARN,
+        ..default()
+    });
+
+    let mut app = App::new();
+    app.add_state::<GameState>()
         .add_plugins((
-            DefaultPlugins
-                .set(ImagePlugin::default_nearest())
-                .set(WindowPlugin {
-                    primary_window: Some(Window {
-                        present_mode: PresentMode::AutoNoVsync,
-                        mode: WindowMode::Windowed,
-                        resolution: WindowResolution::new(1280., 720.),
-                        position: WindowPosition::Centered(MonitorSelection::Primary),
-                        title: String::from("Duhnjyn"),
-                        ..default()
-                    }),
-                    ..default()
-                }),
-            // LogDiagnosticsPlugin::default(),
-            // FrameTimeDiagnosticsPlugin::default(),
-            WorldInspectorPlugin::new(),
+            default_plugins,
             RapierPhysicsPlugin::<NoUserData>::default(),
-            RapierDebugRenderPlugin::default(),
             InputManagerPlugin::<PlayerAction>::default(),
             TilemapPlugin,
+        ))
+        .add_plugins((
             // MainCameraPlugin,
+            RngPlugin,
+            ReplayPlugin,
             PlayerPlugin,
             TilePlugin,
+            FloorPlugin,
+            AutotilePlugin,
             PhysicsPlugin,
             SpriteFlipPlugin,
             AnimationPlugin,
-            MousePositionPlugin,
-            // WeaponPlugin,
+            AimPlugin,
+            SavePlugin,
+            SettingsPlugin,
+            SoundPlugin,
         ))
-        .run();
+        .add_plugins((
+            InventoryPlugin,
+            DoorPlugin,
+            EnemyPlugin,
+            WeaponPlugin,
+            HealthPlugin,
+            ProjectilePlugin,
+            LootPlugin,
+            StatusEffectPlugin,
+            StatsPlugin,
+            TimeControlPlugin,
+            TrapPlugin,
+            LightingPlugin,
+            MinimapPlugin,
+            HudPlugin,
+            MenuPlugin,
+        ))
+        .insert_resource(Time::<Fixed>::from_duration(TIMESTEP))
+        .insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: TIMESTEP.as_secs_f32(),
+                substeps: 1,
+            },
+            ..default()
+        });
+
+    if let Some(err) = settings_err {
+        warn!(
+            "failed to load settings from {}, using defaults: {err}",
+            settings_file.path.display()
+        );
+    }
+    app.insert_resource(settings).insert_resource(settings_file);
+
+    #[cfg(feature = "dev")]
+    app.add_plugins((dev_tools::DevToolsPlugin, console::ConsolePlugin));
+
+    app.run();
 }
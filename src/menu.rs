@@ -0,0 +1,805 @@
+use {
+    super::{
+        console::{self, Console},
+        game_state::{self, GameState},
+        player::PlayerAction,
+        rng::{PinnedSeed, RunSeed},
+        save::{LoadGame, SaveSlots},
+        settings::{InputSettings, Settings, VolumeBus},
+    },
+    bevy::{app::AppExit, prelude::*},
+};
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MenuFocus>()
+            .init_resource::<SettingsReturn>()
+            .init_resource::<Rebinding>()
+            .add_event::<MenuActivated>()
+            .add_systems(OnEnter(GameState::MainMenu), spawn_main_menu)
+            .add_systems(game_state::END_RUN, resume_time)
+            .add_systems(OnEnter(GameState::Settings), spawn_settings_menu)
+            .add_systems(OnEnter(GameState::Paused), (spawn_pause_menu, pause_time))
+            .add_systems(
+                OnTransition {
+                    from: GameState::Paused,
+                    to: GameState::Playing,
+                },
+                resume_time,
+            )
+            .add_systems(OnExit(GameState::MainMenu), despawn_menus)
+            .add_systems(OnExit(GameState::Settings), despawn_menus)
+            .add_systems(OnExit(GameState::Paused), despawn_menus)
+            .add_systems(
+                Update,
+                (
+                    pause
+                        .before(console::toggle_console)
+                        .run_if(in_state(GameState::Playing)),
+                    (
+                        capture_binding,
+                        navigate_menu,
+                        activate_menu_items,
+                        highlight_menu_items,
+                        update_setting_labels,
+                    )
+                        .chain()
+                        .run_if(any_with_component::<MenuRoot>()),
+                ),
+            );
+    }
+}
+
+const FONT_SIZE: f32 = 20.;
+const TITLE_FONT_SIZE: f32 = 36.;
+const BUTTON_COLOR: Color = Color::rgba(0.15, 0.15, 0.2, 0.9);
+const FOCUSED_COLOR: Color = Color::rgba(0.35, 0.35, 0.5, 0.9);
+const FIXED_CONTROLS: [&str; 2] = ["Attack: Left Mouse", "Pause: Esc"];
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuItem {
+    NewGame,
+    Continue,
+    Settings,
+    Quit,
+    Resume,
+    MainMenu,
+    Back,
+    Resolution,
+    DisplayMode,
+    Vsync,
+    Volume(VolumeBus),
+    UiScale,
+    ReduceMotion,
+    Binding(PlayerAction),
+}
+
+impl MenuItem {
+    fn adjustable(self) -> bool {
+        matches!(
+            self,
+            Self::Resolution
+                | Self::DisplayMode
+                | Self::Vsync
+                | Self::Volume(_)
+                | Self::UiScale
+                | Self::ReduceMotion
+        )
+    }
+
+    fn cancels(self) -> bool {
+        matches!(self, Self::Resume | Self::Back)
+    }
+
+    fn label(self, settings: &Settings, rebinding: Option<PlayerAction>) -> String {
+        let percent = |bus| (settings.audio.volume(bus) * 100.).round();
+        match self {
+            Self::NewGame => String::from("New Game"),
+            Self::Continue => String::from("Continue"),
+            Self::Settings => String::from("Settings"),
+            Self::Quit => String::from("Quit"),
+            Self::Resume => String::from("Resume"),
+            Self::MainMenu => String::from("Main Menu"),
+            Self::Back => String::from("Back"),
+            Self::Resolution => {
+                let [width, height] = settings.video.resolution;
+                format!("Resolution: {width}x{height}")
+            }
+            Self::DisplayMode => format!("Window: {:?}", settings.video.display_mode),
+            Self::Vsync => format!("VSync: {}", if settings.video.vsync { "On" } else { "Off" }),
+            Self::Volume(bus) => format!("{bus:?} volume: {}%", percent(bus)),
+            Self::UiScale => format!("UI scale: {}%", settings.accessibility.ui_scale * 100.),
+            Self::ReduceMotion => format!(
+                "Reduce motion: {}",
+                if settings.accessibility.reduce_motion {
+                    "On"
+                } else {
+                    "Off"
+                }
+            ),
+            Self::Binding(action) if rebinding == Some(action) => {
+                format!("{action:?}: press a key")
+            }
+            Self::Binding(action) => format!(
+                "{action:?}: {}",
+                settings.input.key(action).unwrap_or("Unbound")
+            ),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct MenuRoot;
+
+#[derive(Component)]
+pub struct MenuIndex(pub usize);
+
+#[derive(Resource, Default)]
+pub struct MenuFocus(pub usize);
+
+#[derive(Resource)]
+pub struct SettingsReturn(pub GameState);
+
+impl Default for SettingsReturn {
+    fn default() -> Self {
+        Self(GameState::MainMenu)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Rebinding(pub Option<PlayerAction>);
+
+#[derive(Event)]
+pub struct MenuActivated {
+    pub item: MenuItem,
+    pub step: i32,
+}
+
+fn spawn_menu(
+    cmds: &mut Commands,
+    focus: &mut MenuFocus,
+    settings: &Settings,
+    title: &str,
+    items: &[MenuItem],
+    notes: &[&str],
+) {
+    focus.0 = 0;
+    cmds.spawn((
+        Name::new(format!("{title} Menu")),
+        MenuRoot,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(8.),
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.7).into(),
+            z_index: ZIndex::Global(10),
+            ..default()
+        },
+    ))
+    .with_children(|parent| {
+        parent.spawn(TextBundle::from_section(
+            title,
+            TextStyle {
+                font_size: TITLE_FONT_SIZE,
+                ..default()
+            },
+        ));
+        for (idx, &item) in items.iter().enumerate() {
+            parent
+                .spawn((
+                    item,
+                    MenuIndex(idx),
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(280.),
+                            padding: UiRect::all(Val::Px(6.)),
+                            justify_content: JustifyContent::Center,
+                            ..default()
+                        },
+                        background_color: BUTTON_COLOR.into(),
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        item.label(settings, None),
+                        TextStyle {
+                            font_size: FONT_SIZE,
+                            ..default()
+                        },
+                    ));
+                });
+        }
+        for note in notes {
+            parent.spawn(TextBundle::from_section(
+                *note,
+                TextStyle {
+                    font_size: FONT_SIZE * 0.75,
+                    color: Color::GRAY,
+                    ..default()
+                },
+            ));
+        }
+    });
+}
+
+fn spawn_main_menu(
+    mut cmds: Commands,
+    mut focus: ResMut<MenuFocus>,
+    settings: Res<Settings>,
+    slots: Res<SaveSlots>,
+) {
+    let mut items = vec![MenuItem::NewGame];
+    if slots.latest().is_some() {
+        items.push(MenuItem::Continue);
+    }
+    items.extend([MenuItem::Settings, MenuItem::Quit]);
+    spawn_menu(&mut cmds, &mut focus, &settings, "Duhnjyn", &items, &[]);
+}
+
+fn spawn_settings_menu(
+    mut cmds: Commands,
+    mut focus: ResMut<MenuFocus>,
+    mut rebinding: ResMut<Rebinding>,
+    settings: Res<Settings>,
+) {
+    rebinding.0 = None;
+    let mut items = vec![
+        MenuItem::Resolution,
+        MenuItem::DisplayMode,
+        MenuItem::Vsync,
+        MenuItem::Volume(VolumeBus::Master),
+        MenuItem::Volume(VolumeBus::Music),
+        MenuItem::Volume(VolumeBus::Sfx),
+        MenuItem::UiScale,
+        MenuItem::ReduceMotion,
+    ];
+    items.extend(
+        settings
+            .input
+            .keys
+            .iter()
+            .map(|&(action, _)| MenuItem::Binding(action)),
+    );
+    items.push(MenuItem::Back);
+    spawn_menu(
+        &mut cmds,
+        &mut focus,
+        &settings,
+        "Settings",
+        &items,
+        &FIXED_CONTROLS,
+    );
+}
+
+fn spawn_pause_menu(mut cmds: Commands, mut focus: ResMut<MenuFocus>, settings: Res<Settings>) {
+    spawn_menu(
+        &mut cmds,
+        &mut focus,
+        &settings,
+        "Paused",
+        &[MenuItem::Resume, MenuItem::Settings, MenuItem::MainMenu],
+        &[],
+    );
+}
+
+fn despawn_menus(mut cmds: Commands, menu_qry: Query<Entity, With<MenuRoot>>) {
+    for menu_id in menu_qry.iter() {
+        cmds.entity(menu_id).despawn_recursive();
+    }
+}
+
+fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn pause(
+    keys: Res<Input<KeyCode>>,
+    gamepad_btns: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    console: Option<Res<Console>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let start = gamepads.iter().any(|gamepad| {
+        gamepad_btns.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::Start))
+    });
+    let console_open = console.is_some_and(|console| console.open);
+    if !console_open && (keys.just_pressed(KeyCode::Escape) || start) {
+        next_state.set(GameState::Paused);
+    }
+}
+
+fn capture_binding(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<Settings>,
+) {
+    let Some(action) = rebinding.0 else {
+        return;
+    };
+    if keys.just_pressed(KeyCode::Escape) {
+        keys.clear_just_pressed(KeyCode::Escape);
+        rebinding.0 = None;
+        return;
+    }
+    let Some(key_code) = keys
+        .get_just_pressed()
+        .copied()
+        .find(|&key_code| InputSettings::bindable(key_code))
+    else {
+        return;
+    };
+    keys.clear_just_pressed(key_code);
+    settings.input.rebind(action, key_code);
+    rebinding.0 = None;
+}
+
+fn navigate_menu(
+    keys: Res<Input<KeyCode>>,
+    gamepad_btns: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    console: Option<Res<Console>>,
+    rebinding: Res<Rebinding>,
+    mut focus: ResMut<MenuFocus>,
+    mut menu_activated_evw: EventWriter<MenuActivated>,
+    item_qry: Query<(&MenuItem, &MenuIndex, &Interaction)>,
+    changed_item_qry: Query<(&MenuItem, &Interaction), Changed<Interaction>>,
+) {
+    let pressed = |codes: &[KeyCode], btn_type: GamepadButtonType| {
+        keys.any_just_pressed(codes.iter().copied())
+            || gamepads
+                .iter()
+                .any(|gamepad| gamepad_btns.just_pressed(GamepadButton::new(gamepad, btn_type)))
+    };
+    let count = item_qry.iter().count();
+    if count == 0 || console.is_some_and(|console| console.open) || rebinding.0.is_some() {
+        return;
+    }
+
+    for (&item, interaction) in changed_item_qry.iter() {
+        if *interaction == Interaction::Pressed {
+            menu_activated_evw.send(MenuActivated { item, step: 1 });
+        }
+    }
+    for (_, idx, interaction) in item_qry.iter() {
+        if *interaction == Interaction::Hovered && focus.0 != idx.0 {
+            focus.0 = idx.0;
+        }
+    }
+
+    if pressed(&[KeyCode::Up, KeyCode::W], GamepadButtonType::DPadUp) {
+        focus.0 = (focus.0 + count - 1) % count;
+    }
+    if pressed(&[KeyCode::Down, KeyCode::S], GamepadButtonType::DPadDown) {
+        focus.0 = (focus.0 + 1) % count;
+    }
+
+    let focused = item_qry
+        .iter()
+        .find(|(_, idx, _)| idx.0 == focus.0)
+        .map(|(&item, _, _)| item);
+    let step = if pressed(&[KeyCode::Return, KeyCode::Space], GamepadButtonType::South) {
+        Some(1)
+    } else if pressed(&[KeyCode::Right, KeyCode::D], GamepadButtonType::DPadRight) {
+        focused.filter(|item| item.adjustable()).map(|_| 1)
+    } else if pressed(&[KeyCode::Left, KeyCode::A], GamepadButtonType::DPadLeft) {
+        focused.filter(|item| item.adjustable()).map(|_| -1)
+    } else {
+        None
+    };
+    if let (Some(item), Some(step)) = (focused, step) {
+        menu_activated_evw.send(MenuActivated { item, step });
+    }
+
+    if pressed(&[KeyCode::Escape], GamepadButtonType::East) {
+        if let Some((&item, _, _)) = item_qry.iter().find(|(item, _, _)| item.cancels()) {
+            menu_activated_evw.send(MenuActivated { item, step: 1 });
+        }
+    }
+}
+
+fn activate_menu_items(
+    state: Res<State<GameState>>,
+    slots: Res<SaveSlots>,
+    pinned_seed: Option<Res<PinnedSeed>>,
+    mut seed: ResMut<RunSeed>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut settings: ResMut<Settings>,
+    mut settings_return: ResMut<SettingsReturn>,
+    mut rebinding: ResMut<Rebinding>,
+    mut menu_activated_evr: EventReader<MenuActivated>,
+    mut load_evw: EventWriter<LoadGame>,
+    mut app_exit_evw: EventWriter<AppExit>,
+) {
+    for menu_activated in menu_activated_evr.read() {
+        let step = menu_activated.step;
+        match menu_activated.item {
+            MenuItem::NewGame => {
+                if pinned_seed.is_none() {
+                    seed.reroll();
+                }
+                next_state.set(GameState::Playing);
+            }
+            MenuItem::Resume => next_state.set(GameState::Playing),
+            MenuItem::Continue => {
+                if let Some(slot) = slots.latest() {
+                    load_evw.send(LoadGame { slot });
+                    next_state.set(GameState::Playing);
+                }
+            }
+            MenuItem::Settings => {
+                settings_return.0 = state.get().clone();
+                next_state.set(GameState::Settings);
+            }
+            MenuItem::Back => next_state.set(settings_return.0.clone()),
+            MenuItem::MainMenu => next_state.set(GameState::MainMenu),
+            MenuItem::Quit => app_exit_evw.send(AppExit),
+            MenuItem::Resolution => settings.video.cycle_resolution(step),
+            MenuItem::DisplayMode => {
+                settings.video.display_mode = settings.video.display_mode.cycle(step)
+            }
+            MenuItem::Vsync => settings.video.vsync = !settings.video.vsync,
+            MenuItem::Volume(bus) => settings.audio.adjust(bus, step),
+            MenuItem::UiScale => settings.accessibility.adjust_ui_scale(step),
+            MenuItem::ReduceMotion => {
+                settings.accessibility.reduce_motion = !settings.accessibility.reduce_motion
+            }
+            MenuItem::Binding(action) => rebinding.0 = Some(action),
+        }
+    }
+}
+
+fn highlight_menu_items(
+    focus: Res<MenuFocus>,
+    mut item_qry: Query<(&MenuIndex, &mut BackgroundColor), With<MenuItem>>,
+) {
+    for (idx, mut bg_color) in item_qry.iter_mut() {
+        let color = if idx.0 == focus.0 {
+            FOCUSED_COLOR
+        } else {
+            BUTTON_COLOR
+        };
+        if bg_color.0 != color {
+            bg_color.0 = color;
+        }
+    }
+}
+
+fn update_setting_labels(
+    settings: Res<Settings>,
+    rebinding: Res<Rebinding>,
+    item_qry: Query<(&MenuItem, &Children)>,
+    mut text_qry: Query<&mut Text>,
+) {
+    if !settings.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+    for (item, children) in item_qry.iter() {
+        let mut texts = text_qry.iter_many_mut(children.iter());
+        while let Some(mut text) = texts.fetch_next() {
+            text.sections[0].value = item.label(&settings, rebinding.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            hud::Hud,
+            minimap::Minimap,
+            player::Player,
+            save::SaveGame,
+            simulation::{Simulation, SEED},
+        },
+        bevy::input::{
+            gamepad::{GamepadButtonChangedEvent, GamepadConnection, GamepadInfo},
+            keyboard::KeyboardInput,
+            ButtonState,
+        },
+        std::fs,
+    };
+
+    fn press(sim: &mut Simulation, key_code: KeyCode) {
+        for state in [ButtonState::Pressed, ButtonState::Released] {
+            sim.app.world.send_event(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key_code),
+                state,
+                window: Entity::PLACEHOLDER,
+            });
+            sim.app.update();
+        }
+    }
+
+    fn press_button(sim: &mut Simulation, gamepad: Gamepad, btn_type: GamepadButtonType) {
+        for value in [1., 0.] {
+            sim.app
+                .world
+                .send_event(GamepadButtonChangedEvent::new(gamepad, btn_type, value));
+            sim.app.update();
+        }
+    }
+
+    fn state(sim: &Simulation) -> GameState {
+        sim.app.world.resource::<State<GameState>>().get().clone()
+    }
+
+    fn count<C: Component>(sim: &mut Simulation) -> usize {
+        sim.app
+            .world
+            .query_filtered::<(), With<C>>()
+            .iter(&sim.app.world)
+            .count()
+    }
+
+    fn item_id(sim: &mut Simulation, item: MenuItem) -> Entity {
+        sim.app
+            .world
+            .query::<(Entity, &MenuItem)>()
+            .iter(&sim.app.world)
+            .find(|(_, &other)| other == item)
+            .map(|(id, _)| id)
+            .unwrap()
+    }
+
+    fn in_main_menu() -> Simulation {
+        Simulation::with(|app| {
+            app.insert_resource(NextState::<GameState>(None));
+        })
+    }
+
+    #[test]
+    fn pause_menu_opens_settings_and_resumes() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+
+        press(&mut sim, KeyCode::Escape);
+        assert_eq!(state(&sim), GameState::Paused);
+        assert!(sim.app.world.resource::<Time<Virtual>>().is_paused());
+        let frozen = sim.player::<Transform>().translation;
+
+        press(&mut sim, KeyCode::Down);
+        press(&mut sim, KeyCode::Return);
+        assert_eq!(state(&sim), GameState::Settings);
+
+        press(&mut sim, KeyCode::Right);
+        assert_eq!(
+            sim.app.world.resource::<Settings>().video.resolution,
+            [1600, 900]
+        );
+        for _ in 0..3 {
+            press(&mut sim, KeyCode::Down);
+        }
+        press(&mut sim, KeyCode::Left);
+        assert_eq!(sim.app.world.resource::<Settings>().audio.master, 0.9);
+
+        press(&mut sim, KeyCode::Escape);
+        assert_eq!(state(&sim), GameState::Paused);
+        assert!(sim.app.world.resource::<Time<Virtual>>().is_paused());
+        assert_eq!(sim.player::<Transform>().translation, frozen);
+
+        press(&mut sim, KeyCode::Return);
+        assert_eq!(state(&sim), GameState::Playing);
+        assert!(!sim.app.world.resource::<Time<Virtual>>().is_paused());
+        assert_eq!(
+            sim.app
+                .world
+                .query_filtered::<(), With<MenuRoot>>()
+                .iter(&sim.app.world)
+                .count(),
+            0
+        );
+        assert_eq!(
+            sim.app
+                .world
+                .query_filtered::<(), With<crate::player::Player>>()
+                .iter(&sim.app.world)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn main_menu_starts_and_ends_runs() {
+        let mut sim = in_main_menu();
+        assert_eq!(state(&sim), GameState::MainMenu);
+        assert_eq!(count::<Player>(&mut sim), 0);
+
+        press(&mut sim, KeyCode::Return);
+        assert_eq!(state(&sim), GameState::Playing);
+        assert_eq!(*sim.app.world.resource::<RunSeed>(), RunSeed(SEED));
+        sim.settle(256);
+
+        press(&mut sim, KeyCode::Escape);
+        press(&mut sim, KeyCode::Down);
+        press(&mut sim, KeyCode::Down);
+        press(&mut sim, KeyCode::Return);
+        assert_eq!(state(&sim), GameState::MainMenu);
+        assert!(!sim.app.world.resource::<Time<Virtual>>().is_paused());
+        assert_eq!(count::<Player>(&mut sim), 0);
+        assert_eq!(count::<Hud>(&mut sim), 0);
+        assert_eq!(count::<Minimap>(&mut sim), 0);
+
+        press(&mut sim, KeyCode::Return);
+        assert_eq!(state(&sim), GameState::Playing);
+        assert_eq!(count::<Player>(&mut sim), 1);
+        assert_eq!(count::<Hud>(&mut sim), 1);
+        assert_eq!(count::<Minimap>(&mut sim), 1);
+        assert_eq!(count::<MenuRoot>(&mut sim), 0);
+        sim.settle(256);
+    }
+
+    #[test]
+    fn new_games_reroll_unpinned_seeds() {
+        let mut sim = in_main_menu();
+        sim.app.world.remove_resource::<PinnedSeed>();
+        let mut seeds = vec![*sim.app.world.resource::<RunSeed>()];
+        for _ in 0..2 {
+            press(&mut sim, KeyCode::Return);
+            assert_eq!(state(&sim), GameState::Playing);
+            seeds.push(*sim.app.world.resource::<RunSeed>());
+            sim.app
+                .world
+                .resource_mut::<NextState<GameState>>()
+                .set(GameState::MainMenu);
+            sim.app.update();
+        }
+
+        assert_ne!(seeds[0], seeds[1]);
+        assert_ne!(seeds[1], seeds[2]);
+    }
+
+    #[test]
+    fn continue_loads_the_latest_save() {
+        let dir = std::env::temp_dir().join(format!("duhnjyn-menu-test-{}", std::process::id()));
+        let mut sim = Simulation::with(|app| {
+            app.insert_resource(SaveSlots { dir: dir.clone() });
+        });
+        sim.settle(256);
+        sim.step_n(20, &[PlayerAction::MoveRight]);
+        sim.app.world.send_event(SaveGame { slot: 0 });
+        sim.step(&[]);
+        let saved = sim.app.world.resource::<SaveSlots>().read(0).unwrap();
+
+        sim.app
+            .world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::MainMenu);
+        sim.app.update();
+        assert_eq!(count::<Player>(&mut sim), 0);
+
+        press(&mut sim, KeyCode::Down);
+        press(&mut sim, KeyCode::Return);
+        assert_eq!(state(&sim), GameState::Playing);
+        assert_eq!(
+            sim.player::<Transform>().translation,
+            Vec3::from_array(saved.player.translation)
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn mouse_hovers_and_clicks_menu_items() {
+        let mut sim = in_main_menu();
+        let settings_id = item_id(&mut sim, MenuItem::Settings);
+        let settings_idx = sim.app.world.get::<MenuIndex>(settings_id).unwrap().0;
+
+        sim.app
+            .world
+            .entity_mut(settings_id)
+            .insert(Interaction::Hovered);
+        sim.app.update();
+        assert_eq!(sim.app.world.resource::<MenuFocus>().0, settings_idx);
+
+        sim.app
+            .world
+            .entity_mut(settings_id)
+            .insert(Interaction::Pressed);
+        sim.app.update();
+        sim.app.update();
+        assert_eq!(state(&sim), GameState::Settings);
+
+        let vsync = sim.app.world.resource::<Settings>().video.vsync;
+        let vsync_id = item_id(&mut sim, MenuItem::Vsync);
+        sim.app
+            .world
+            .entity_mut(vsync_id)
+            .insert(Interaction::Pressed);
+        sim.app.update();
+        assert_eq!(sim.app.world.resource::<Settings>().video.vsync, !vsync);
+    }
+
+    #[test]
+    fn gamepad_drives_the_pause_menu() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let gamepad = Gamepad::new(0);
+        sim.app.world.send_event(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected(GamepadInfo {
+                name: String::from("pad"),
+            }),
+        ));
+        sim.step(&[]);
+
+        press_button(&mut sim, gamepad, GamepadButtonType::Start);
+        assert_eq!(state(&sim), GameState::Paused);
+        press_button(&mut sim, gamepad, GamepadButtonType::DPadDown);
+        press_button(&mut sim, gamepad, GamepadButtonType::South);
+        assert_eq!(state(&sim), GameState::Settings);
+        press_button(&mut sim, gamepad, GamepadButtonType::East);
+        assert_eq!(state(&sim), GameState::Paused);
+
+        press_button(&mut sim, gamepad, GamepadButtonType::DPadUp);
+        press_button(&mut sim, gamepad, GamepadButtonType::South);
+        assert_eq!(state(&sim), GameState::MainMenu);
+        assert_eq!(count::<Player>(&mut sim), 0);
+    }
+
+    #[test]
+    fn settings_rebind_keys_and_swap_conflicts() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let key = |sim: &Simulation, action| {
+            sim.app
+                .world
+                .resource::<Settings>()
+                .input
+                .key(action)
+                .map(String::from)
+        };
+
+        press(&mut sim, KeyCode::Escape);
+        press(&mut sim, KeyCode::Down);
+        press(&mut sim, KeyCode::Return);
+        assert_eq!(state(&sim), GameState::Settings);
+        for _ in 0..8 {
+            press(&mut sim, KeyCode::Down);
+        }
+
+        press(&mut sim, KeyCode::Return);
+        assert_eq!(
+            sim.app.world.resource::<Rebinding>().0,
+            Some(PlayerAction::MoveLeft)
+        );
+        press(&mut sim, KeyCode::Escape);
+        assert_eq!(state(&sim), GameState::Settings);
+        assert_eq!(sim.app.world.resource::<Rebinding>().0, None);
+        assert_eq!(key(&sim, PlayerAction::MoveLeft).as_deref(), Some("A"));
+
+        press(&mut sim, KeyCode::Return);
+        press(&mut sim, KeyCode::J);
+        assert_eq!(key(&sim, PlayerAction::MoveLeft).as_deref(), Some("J"));
+
+        press(&mut sim, KeyCode::Down);
+        press(&mut sim, KeyCode::Return);
+        press(&mut sim, KeyCode::J);
+        assert_eq!(key(&sim, PlayerAction::MoveRight).as_deref(), Some("J"));
+        assert_eq!(key(&sim, PlayerAction::MoveLeft).as_deref(), Some("D"));
+        let labels = sim
+            .app
+            .world
+            .query::<&Text>()
+            .iter(&sim.app.world)
+            .map(|text| text.sections[0].value.clone())
+            .collect::<Vec<_>>();
+        assert!(labels.contains(&String::from("MoveRight: J")));
+        assert!(labels.contains(&String::from("MoveLeft: D")));
+    }
+}
@@ -0,0 +1,417 @@
+use {
+    super::{
+        door::Door,
+        enemy::Enemy,
+        floor::{FloorLayout, FloorState},
+        game_state::{self, GameState},
+        inventory::Pickup,
+        lighting::{self, LightGrid},
+        player::{Player, PlayerAction},
+        tile::{self, TileChanged, TileWorld, TILEMAP_SIZE},
+    },
+    bevy::{
+        prelude::*,
+        render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    leafwing_input_manager::prelude::*,
+};
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(game_state::START_RUN, spawn_maps)
+            .add_systems(
+                game_state::END_RUN,
+                (
+                    game_state::despawn_with::<Minimap>,
+                    game_state::despawn_with::<FullMap>,
+                ),
+            )
+            .add_systems(Update, toggle_map.run_if(in_state(GameState::Playing)))
+            .add_systems(
+                PostUpdate,
+                draw_maps
+                    .after(lighting::update_light_grid)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+const MINIMAP_SIZE: u32 = 64;
+const MINIMAP_SCALE: f32 = 2.;
+const FULL_MAP_SCALE: f32 = 2.;
+const WALL_COLOR: [u8; 4] = [200, 200, 200, 255];
+const FLOOR_COLOR: [u8; 4] = [40, 40, 48, 160];
+const PLAYER_COLOR: [u8; 4] = [80, 220, 80, 255];
+const DOOR_COLOR: [u8; 4] = [200, 140, 60, 255];
+const PICKUP_COLOR: [u8; 4] = [240, 210, 60, 255];
+const ENEMY_COLOR: [u8; 4] = [220, 50, 50, 255];
+
+#[derive(Component)]
+pub struct Minimap;
+
+#[derive(Component)]
+pub struct FullMap;
+
+#[derive(Component)]
+pub struct FullMapImage;
+
+#[derive(Resource)]
+pub struct MapImages {
+    pub minimap: Handle<Image>,
+    pub full: Handle<Image>,
+}
+
+#[derive(Default)]
+pub struct MapCache {
+    markers: Vec<(IVec2, [u8; 4])>,
+    full_stale: bool,
+}
+
+pub fn map_image(size: UVec2) -> Image {
+    Image::new_fill(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0; 4],
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+pub fn full_map_bounds(layout: &FloorLayout) -> (IVec2, UVec2) {
+    let (min, max) = layout
+        .rooms
+        .keys()
+        .fold((IVec2::ZERO, IVec2::ZERO), |(min, max), &room| {
+            (min.min(room), max.max(room))
+        });
+    let room_size = IVec2::new(TILEMAP_SIZE.x as i32, TILEMAP_SIZE.y as i32);
+    let center = (min + max) * room_size / 2;
+    let size = (max - min + IVec2::ONE) * room_size;
+    (center, size.as_uvec2())
+}
+
+pub fn draw_map(
+    image: &mut Image,
+    center: IVec2,
+    tile_world: &TileWorld,
+    floor_state: &FloorState,
+    markers: &[(IVec2, [u8; 4])],
+) {
+    let size = IVec2::new(
+        image.texture_descriptor.size.width as i32,
+        image.texture_descriptor.size.height as i32,
+    );
+    let min = center - size / 2;
+    let pixel = |tile: IVec2| {
+        let local = tile - min;
+        (local.cmpge(IVec2::ZERO).all() && local.cmplt(size).all())
+            .then(|| ((size.y - 1 - local.y) * size.x + local.x) as usize * 4)
+    };
+
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let tile = min + IVec2::new(x, y);
+            let color = match (
+                floor_state.explored.contains(&tile),
+                tile_world.get(tile).is_some(),
+            ) {
+                (false, _) => [0; 4],
+                (true, true) => WALL_COLOR,
+                (true, false) => FLOOR_COLOR,
+            };
+            let i = pixel(tile).unwrap();
+            image.data[i..i + 4].copy_from_slice(&color);
+        }
+    }
+    for &(tile, color) in markers {
+        if let Some(i) = pixel(tile) {
+            image.data[i..i + 4].copy_from_slice(&color);
+        }
+    }
+}
+
+fn spawn_maps(mut cmds: Commands, mut images: ResMut<Assets<Image>>) {
+    let map_images = MapImages {
+        minimap: images.add(map_image(UVec2::splat(MINIMAP_SIZE))),
+        full: images.add(map_image(UVec2::new(TILEMAP_SIZE.x, TILEMAP_SIZE.y))),
+    };
+    cmds.spawn((
+        Name::new("Minimap"),
+        Minimap,
+        ImageBundle {
+            image: UiImage::new(map_images.minimap.clone()),
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.),
+                right: Val::Px(8.),
+                width: Val::Px(MINIMAP_SIZE as f32 * MINIMAP_SCALE),
+                height: Val::Px(MINIMAP_SIZE as f32 * MINIMAP_SCALE),
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.5).into(),
+            ..default()
+        },
+    ));
+    cmds.spawn((
+        Name::new("Full Map"),
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.8).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        FullMap,
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            FullMapImage,
+            ImageBundle {
+                image: UiImage::new(map_images.full.clone()),
+                style: Style {
+                    width: Val::Px(TILEMAP_SIZE.x as f32 * FULL_MAP_SCALE),
+                    height: Val::Px(TILEMAP_SIZE.y as f32 * FULL_MAP_SCALE),
+                    ..default()
+                },
+                ..default()
+            },
+        ));
+    });
+    cmds.insert_resource(map_images);
+}
+
+fn toggle_map(
+    player_qry: Query<&ActionState<PlayerAction>, With<Player>>,
+    mut minimap_qry: Query<&mut Visibility, (With<Minimap>, Without<FullMap>)>,
+    mut full_map_qry: Query<&mut Visibility, With<FullMap>>,
+) {
+    let Ok(player_actions) = player_qry.get_single() else {
+        return;
+    };
+    if !player_actions.just_pressed(PlayerAction::ToggleMap) {
+        return;
+    }
+    for mut full_map_vis in full_map_qry.iter_mut() {
+        let open = *full_map_vis == Visibility::Hidden;
+        *full_map_vis = if open {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        for mut minimap_vis in minimap_qry.iter_mut() {
+            *minimap_vis = if open {
+                Visibility::Hidden
+            } else {
+                Visibility::Inherited
+            };
+        }
+    }
+}
+
+fn draw_maps(
+    mut cache: Local<MapCache>,
+    mut tile_changed_evr: EventReader<TileChanged>,
+    map_images: Option<Res<MapImages>>,
+    mut images: ResMut<Assets<Image>>,
+    tile_world: Res<TileWorld>,
+    floor_state: Res<FloorState>,
+    layout: Res<FloorLayout>,
+    light_grid: Res<LightGrid>,
+    player_qry: Query<&Transform, With<Player>>,
+    door_qry: Query<&Transform, With<Door>>,
+    pickup_qry: Query<&Transform, With<Pickup>>,
+    enemy_qry: Query<&Transform, With<Enemy>>,
+    full_map_qry: Query<&Visibility, With<FullMap>>,
+    mut full_map_image_qry: Query<&mut Style, With<FullMapImage>>,
+) {
+    let (Some(map_images), Ok(player_xform)) = (map_images, player_qry.get_single()) else {
+        return;
+    };
+    let tile_of = |xform: &Transform| tile::tile_at(xform.translation.truncate());
+    let player_tile = tile_of(player_xform);
+
+    let mut markers = door_qry
+        .iter()
+        .map(|door_xform| (tile_of(door_xform), DOOR_COLOR))
+        .chain(
+            pickup_qry
+                .iter()
+                .map(|pickup_xform| (tile_of(pickup_xform), PICKUP_COLOR)),
+        )
+        .filter(|(tile, _)| floor_state.explored.contains(tile))
+        .chain(
+            enemy_qry
+                .iter()
+                .map(tile_of)
+                .filter(|&tile| light_grid.level(tile) > 0.)
+                .map(|tile| (tile, ENEMY_COLOR)),
+        )
+        .collect::<Vec<_>>();
+    markers.push((player_tile, PLAYER_COLOR));
+
+    let tiles_changed = tile_changed_evr.read().count() > 0;
+    if tiles_changed || floor_state.is_changed() || layout.is_changed() || markers != cache.markers
+    {
+        if let Some(minimap) = images.get_mut(&map_images.minimap) {
+            draw_map(minimap, player_tile, &tile_world, &floor_state, &markers);
+        }
+        cache.markers = markers;
+        cache.full_stale = true;
+    }
+
+    if !cache.full_stale
+        || full_map_qry
+            .iter()
+            .all(|full_map_vis| *full_map_vis == Visibility::Hidden)
+    {
+        return;
+    }
+    cache.full_stale = false;
+    let (center, size) = full_map_bounds(&layout);
+    let Some(full) = images.get_mut(&map_images.full) else {
+        return;
+    };
+    let extent = Extent3d {
+        width: size.x,
+        height: size.y,
+        depth_or_array_layers: 1,
+    };
+    if full.texture_descriptor.size != extent {
+        full.resize(extent);
+        for mut style in full_map_image_qry.iter_mut() {
+            style.width = Val::Px(size.x as f32 * FULL_MAP_SCALE);
+            style.height = Val::Px(size.y as f32 * FULL_MAP_SCALE);
+        }
+    }
+    draw_map(full, center, &tile_world, &floor_state, &cache.markers);
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::simulation::Simulation};
+
+    #[test]
+    fn map_shows_explored_tiles_and_markers() {
+        let mut tile_world = TileWorld::default();
+        let mut floor_state = FloorState::default();
+        for x in -2..=2 {
+            tile_world.set(IVec2::new(x, -1), default());
+        }
+        floor_state
+            .explored
+            .extend((-2..=2).flat_map(|x| [IVec2::new(x, -1), IVec2::new(x, 0)]));
+        tile_world.set(IVec2::new(0, 5), default());
+
+        let mut image = map_image(UVec2::splat(16));
+        draw_map(
+            &mut image,
+            IVec2::ZERO,
+            &tile_world,
+            &floor_state,
+            &[
+                (IVec2::new(1, 0), PLAYER_COLOR),
+                (IVec2::new(99, 0), ENEMY_COLOR),
+            ],
+        );
+        let pixel = |tile: IVec2| {
+            let i = ((15 - (tile.y + 8)) * 16 + tile.x + 8) as usize * 4;
+            [
+                image.data[i],
+                image.data[i + 1],
+                image.data[i + 2],
+                image.data[i + 3],
+            ]
+        };
+        assert_eq!(pixel(IVec2::new(-2, -1)), WALL_COLOR);
+        assert_eq!(pixel(IVec2::new(0, 0)), FLOOR_COLOR);
+        assert_eq!(pixel(IVec2::new(1, 0)), PLAYER_COLOR);
+        assert_eq!(pixel(IVec2::new(0, 5)), [0; 4]);
+    }
+
+    #[test]
+    fn full_map_covers_wide_layouts() {
+        let layout = FloorLayout {
+            seed: None,
+            rooms: (-5..=5).map(|x| (IVec2::new(x, 0), Vec::new())).collect(),
+        };
+        let (center, size) = full_map_bounds(&layout);
+        assert_eq!(center, IVec2::ZERO);
+        assert_eq!(size, UVec2::new(11 * TILEMAP_SIZE.x, TILEMAP_SIZE.y));
+    }
+
+    #[test]
+    fn maps_only_redraw_when_something_changes() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        sim.step_n(4, &[]);
+        let minimap_id = sim.app.world.resource::<MapImages>().minimap.id();
+        let redrawn = |sim: &mut Simulation| {
+            sim.app
+                .world
+                .resource_mut::<Events<AssetEvent<Image>>>()
+                .drain()
+                .any(|asset_ev| asset_ev.is_modified(minimap_id))
+        };
+        redrawn(&mut sim);
+
+        sim.step_n(4, &[]);
+        assert!(!redrawn(&mut sim));
+        sim.step_n(32, &[PlayerAction::MoveRight]);
+        assert!(redrawn(&mut sim));
+    }
+
+    #[test]
+    fn toggle_map_swaps_minimap_for_full_map() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let visibility = |sim: &mut Simulation| {
+            let minimap_vis = *sim
+                .app
+                .world
+                .query_filtered::<&Visibility, With<Minimap>>()
+                .single(&sim.app.world);
+            let full_map_vis = *sim
+                .app
+                .world
+                .query_filtered::<&Visibility, With<FullMap>>()
+                .single(&sim.app.world);
+            (minimap_vis, full_map_vis)
+        };
+        assert_eq!(
+            visibility(&mut sim),
+            (Visibility::Inherited, Visibility::Hidden)
+        );
+
+        sim.step(&[PlayerAction::ToggleMap]);
+        assert_eq!(
+            visibility(&mut sim),
+            (Visibility::Hidden, Visibility::Inherited)
+        );
+        sim.step(&[]);
+        sim.step(&[PlayerAction::ToggleMap]);
+        assert_eq!(
+            visibility(&mut sim),
+            (Visibility::Inherited, Visibility::Hidden)
+        );
+
+        let map_images = sim.app.world.resource::<MapImages>();
+        let minimap = sim
+            .app
+            .world
+            .resource::<Assets<Image>>()
+            .get(&map_images.minimap);
+        assert!(minimap
+            .is_some_and(|minimap| minimap.data.chunks(4).any(|pixel| pixel == PLAYER_COLOR)));
+    }
+}
@@ -1,4 +1,9 @@
-use {super::game_state::GameState, bevy::prelude::*, bevy_rapier2d::prelude::*};
+use {
+    super::{game_state::GameState, time_control::TimeScale},
+    bevy::prelude::*,
+    bevy_rapier2d::prelude::*,
+    std::time::Duration,
+};
 
 pub struct PhysicsPlugin;
 
@@ -13,12 +18,21 @@ impl Plugin for PhysicsPlugin {
     }
 }
 
+pub const TIMESTEP: Duration = Duration::from_micros(15625);
+
+pub const TILE_GROUP: Group = Group::GROUP_1;
+pub const ACTOR_GROUP: Group = Group::GROUP_2;
+pub const ITEM_GROUP: Group = Group::GROUP_3;
+
 #[derive(Component, Default, Deref, DerefMut)]
 pub struct TerminalVelocity(pub Vec2);
 
 #[derive(Component, Default, Deref, DerefMut)]
 pub struct Acceleration(pub Vec2);
 
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct JumpVelocity(pub f32);
+
 #[derive(Component, Default)]
 pub struct NetDirection {
     pub x: i8,
@@ -46,20 +60,24 @@ pub fn apply_forces(
         &Friction,
         &Acceleration,
         &NetDirection,
+        Option<&TimeScale>,
     )>,
     time: Res<Time<Fixed>>,
 ) {
-    let dt = time.timestep().as_secs_f32();
-
-    for (mut kcc, mut vel, terminal_vel, friction, acc, net_dir) in physics_qry.iter_mut() {
+    for (mut kcc, mut vel, terminal_vel, friction, acc, net_dir, time_scale) in
+        physics_qry.iter_mut()
+    {
+        let scale = time_scale.map_or(1., |time_scale| time_scale.0);
+        let dt = time.timestep().as_secs_f32() * scale;
+        let friction = friction.coefficient * scale;
         vel.linvel.x += acc.x * net_dir.x as f32 * dt;
         vel.linvel.y += acc.y * net_dir.y as f32 * dt;
 
         let dir = vel.linvel.normalize_or_zero();
         if dir.x > 0. {
-            vel.linvel.x = f32::max(0., vel.linvel.x - friction.coefficient);
+            vel.linvel.x = f32::max(0., vel.linvel.x - friction);
         } else if dir.x < 0. {
-            vel.linvel.x = f32::min(vel.linvel.x + friction.coefficient, 0.);
+            vel.linvel.x = f32::min(vel.linvel.x + friction, 0.);
         }
         vel.linvel.x = vel.linvel.x.clamp(-terminal_vel.x, terminal_vel.x);
         vel.linvel.y = vel.linvel.y.clamp(-terminal_vel.y, terminal_vel.y);
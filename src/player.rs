@@ -1,61 +1,115 @@
 use {
     super::{
+        aim::{AimDevices, AimSource, AimTarget},
         animation::{self, AnimationIndices, AnimationTimer},
-        game_state::GameState,
-        mouse_position::MousePosition,
-        physics::{self, Acceleration, Grounded, NetDirection, TerminalVelocity},
+        console::{self, AddConsoleCommand},
+        game_state::{self, GameState},
+        health::{Health, Invulnerable},
+        inventory::{EquippedWeapon, Inventory, ItemStack},
+        lighting::LightSource,
+        physics::{
+            self, Acceleration, Grounded, JumpVelocity, NetDirection, TerminalVelocity, ACTOR_GROUP,
+        },
+        settings::Settings,
         sprite_flip::Flippable,
-        weapon::Weapon,
+        stats::{Experience, Stat, Stats},
+        status_effect::StatusEffects,
     },
-    bevy::{prelude::*, sprite::Anchor},
+    bevy::prelude::*,
     bevy_rapier2d::prelude::*,
-    leafwing_input_manager::prelude::*,
+    leafwing_input_manager::{plugin::InputManagerSystem, prelude::*},
+    serde::{Deserialize, Serialize},
 };
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Playing), spawn_player)
+        app.add_systems(game_state::START_RUN, spawn_player)
+            .add_systems(
+                PreUpdate,
+                buffer_player_actions
+                    .after(InputManagerSystem::Update)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(game_state::END_RUN, game_state::despawn_with::<Player>)
             .add_systems(
                 Update,
-                (
-                    discrete_player_input,
-                    update_animation_state.before(animation::adjust_sprite_indices),
-                )
+                update_animation_state
+                    .before(animation::adjust_sprite_indices)
                     .run_if(in_state(GameState::Playing)),
             )
             .add_systems(
                 FixedUpdate,
-                player_movement
+                (latch_player_actions, discrete_player_input, player_movement)
+                    .chain()
                     .after(physics::process_collisions)
                     .before(physics::apply_forces)
-                    .run_if(in_state(GameState::Playing)),
+                    .distributive_run_if(in_state(GameState::Playing)),
+            )
+            .add_console_command("tp", "tp <x> <y>: teleport the player", teleport)
+            .add_console_command("god", "toggle player invulnerability", toggle_god_mode)
+            .add_console_command(
+                "noclip",
+                "toggle player collisions and gravity, fly with up and down",
+                toggle_noclip,
             );
     }
 }
 
-#[derive(Actionlike, Clone, Eq, PartialEq, Hash, Reflect)]
+#[derive(Actionlike, Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Hash, Reflect)]
 pub enum PlayerAction {
     MoveLeft,
     MoveRight,
+    MoveUp,
+    MoveDown,
     Jump,
     Attack,
+    CycleEquipment,
+    Interact,
+    ToggleMap,
 }
 
+pub const PLAYER_INVENTORY_SLOTS: usize = 8;
+pub const WALKING: AnimationIndices = AnimationIndices { first: 6, last: 19 };
+pub const FOOTSTEP_FRAMES: [usize; 2] = [WALKING.first + 3, WALKING.first + 10];
+
 #[derive(Component, Default)]
 pub struct Player {
-    can_jump: bool,
+    pub can_jump: bool,
+    pub noclip: bool,
+}
+
+#[derive(Component, Default)]
+pub struct TickActions {
+    pressed: Vec<PlayerAction>,
+    previous: Vec<PlayerAction>,
+    buffered: Vec<PlayerAction>,
+}
+
+impl TickActions {
+    pub fn pressed(&self, action: PlayerAction) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: PlayerAction) -> bool {
+        self.pressed(action) && !self.previous.contains(&action)
+    }
 }
 
 #[derive(Component)]
 pub struct PlayerCamera;
 
-fn spawn_player(
+pub fn spawn_player(
     mut cmds: Commands,
     asset_server: Res<AssetServer>,
     mut tex_atlases: ResMut<Assets<TextureAtlas>>,
+    settings: Res<Settings>,
 ) {
+    let mut inventory = Inventory::new(PLAYER_INVENTORY_SLOTS);
+    inventory.add(ItemStack::new("sword", 1), 1);
+    inventory.equipped = Some(0);
+
     cmds.spawn((
         Player::default(),
         Name::new("Player"),
@@ -76,46 +130,55 @@ fn spawn_player(
             ..default()
         },
         InputManagerBundle::<PlayerAction> {
-            input_map: InputMap::new([
-                (KeyCode::A, PlayerAction::MoveLeft),
-                (KeyCode::D, PlayerAction::MoveRight),
-                (KeyCode::Space, PlayerAction::Jump),
-            ])
-            .insert(MouseButton::Left, PlayerAction::Attack)
-            .clone(),
+            input_map: settings.input.input_map(),
             ..default()
         },
-        KinematicCharacterController::default(),
-        Collider::capsule_y(4.5, 6.),
-        Friction::coefficient(3.),
-        Velocity::zero(),
-        TerminalVelocity(Vec2::new(50., 200.)),
-        Acceleration(Vec2::new(300., 500.)),
-        NetDirection { x: 0, y: -1 },
-        Grounded::default(),
+        TickActions::default(),
+        (
+            KinematicCharacterController {
+                filter_flags: QueryFilterFlags::EXCLUDE_SENSORS,
+                ..default()
+            },
+            Collider::capsule_y(4.5, 6.),
+            CollisionGroups::new(ACTOR_GROUP, Group::ALL),
+            Friction::coefficient(3.),
+            Velocity::zero(),
+            TerminalVelocity::default(),
+            Acceleration::default(),
+            JumpVelocity::default(),
+            NetDirection { x: 0, y: -1 },
+            Grounded::default(),
+        ),
+        (Health::new(5), StatusEffects::default()),
+        (
+            Stats::new([
+                (Stat::MoveSpeed, 50.),
+                (Stat::FallSpeed, 200.),
+                (Stat::Acceleration, 300.),
+                (Stat::Gravity, 350.),
+                (Stat::JumpVelocity, 200.),
+                (Stat::MaxHealth, 5.),
+            ]),
+            Experience::default(),
+        ),
+        (inventory, EquippedWeapon::default()),
         Flippable::default(),
+        (
+            AimTarget::default(),
+            AimSource::Cursor,
+            AimDevices {
+                cursor: true,
+                gamepad: None,
+            },
+        ),
         AnimationIndices { first: 0, last: 0 },
         AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
+        LightSource::LANTERN,
     ))
     .with_children(|parent| {
         let mut player_cam = Camera2dBundle::default();
         player_cam.projection.scale /= 3.;
         parent.spawn((PlayerCamera, player_cam));
-
-        parent.spawn((
-            SpriteBundle {
-                sprite: Sprite {
-                    anchor: Anchor::BottomCenter,
-                    ..default()
-                },
-                transform: Transform::from_xyz(0., 5., -1.),
-                texture: asset_server.load("sword.png"),
-                ..default()
-            },
-            Flippable::default(),
-            Weapon,
-            Name::new("Sword"),
-        ));
     });
 }
 
@@ -140,7 +203,6 @@ fn update_animation_state(
     ) = player_qry.single_mut();
 
     let jumping = AnimationIndices { first: 5, last: 5 };
-    let walking = AnimationIndices { first: 6, last: 19 };
     let idling = AnimationIndices { first: 0, last: 0 };
     let attacking = AnimationIndices { first: 1, last: 4 };
 
@@ -156,20 +218,40 @@ fn update_animation_state(
             *player_animation_indices = jumping;
         }
     } else if player_net_dir.x != 0 {
-        if *player_animation_indices != walking && !attack_in_progress {
-            *player_animation_indices = walking;
+        if *player_animation_indices != WALKING && !attack_in_progress {
+            *player_animation_indices = WALKING;
         }
     } else if *player_animation_indices != idling && !attack_in_progress {
         *player_animation_indices = idling;
     }
 }
 
-fn discrete_player_input(
-    mut player_qry: Query<(&mut Player, &ActionState<PlayerAction>, &Grounded)>,
-) {
-    let (mut player, player_actions, player_grounded) = player_qry.single_mut();
+fn buffer_player_actions(mut player_qry: Query<(&ActionState<PlayerAction>, &mut TickActions)>) {
+    for (player_actions, mut tick_actions) in player_qry.iter_mut() {
+        for action in player_actions.get_just_pressed() {
+            if !tick_actions.buffered.contains(&action) {
+                tick_actions.buffered.push(action);
+            }
+        }
+    }
+}
 
-    if player_actions.just_pressed(PlayerAction::Jump) && player_grounded.0 {
+pub fn latch_player_actions(mut player_qry: Query<(&ActionState<PlayerAction>, &mut TickActions)>) {
+    for (player_actions, mut tick_actions) in player_qry.iter_mut() {
+        let mut pressed = player_actions.get_pressed();
+        for action in std::mem::take(&mut tick_actions.buffered) {
+            if !pressed.contains(&action) {
+                pressed.push(action);
+            }
+        }
+        tick_actions.previous = std::mem::replace(&mut tick_actions.pressed, pressed);
+    }
+}
+
+pub fn discrete_player_input(mut player_qry: Query<(&mut Player, &TickActions, &Grounded)>) {
+    let (mut player, player_tick_actions, player_grounded) = player_qry.single_mut();
+
+    if player_tick_actions.just_pressed(PlayerAction::Jump) && player_grounded.0 {
         player.can_jump = true;
     }
 }
@@ -177,44 +259,215 @@ fn discrete_player_input(
 pub fn player_movement(
     mut player_qry: Query<(
         &mut Player,
-        &ActionState<PlayerAction>,
+        &TickActions,
         &Transform,
         &mut Velocity,
         &mut NetDirection,
         &mut Grounded,
         &mut Flippable,
+        &AimTarget,
+        &JumpVelocity,
     )>,
-    mouse_pos: Res<MousePosition>,
 ) {
     let (
         mut player,
-        player_actions,
+        player_tick_actions,
         player_xform,
         mut player_vel,
         mut player_net_dir,
         mut player_grounded,
         mut player_flippable,
+        player_aim_target,
+        player_jump_vel,
     ) = player_qry.single_mut();
 
-    if player_actions.released(PlayerAction::MoveLeft)
-        && player_actions.released(PlayerAction::MoveRight)
+    if !player_tick_actions.pressed(PlayerAction::MoveLeft)
+        && !player_tick_actions.pressed(PlayerAction::MoveRight)
     {
         player_net_dir.x = 0;
     }
-    if player_actions.pressed(PlayerAction::MoveLeft) {
+    if player_tick_actions.pressed(PlayerAction::MoveLeft) {
         player_net_dir.x = -1;
         player_flippable.flip_x = true;
     }
-    if player_actions.pressed(PlayerAction::MoveRight) {
+    if player_tick_actions.pressed(PlayerAction::MoveRight) {
         player_net_dir.x = 1;
         player_flippable.flip_x = false;
     }
-    if player_actions.pressed(PlayerAction::Attack) {
-        player_flippable.flip_x = player_xform.translation.x > mouse_pos.x;
+    if player.noclip {
+        player_net_dir.y = player_tick_actions.pressed(PlayerAction::MoveUp) as i8
+            - player_tick_actions.pressed(PlayerAction::MoveDown) as i8;
+        if player_net_dir.y == 0 {
+            player_vel.linvel.y = 0.;
+        }
+    }
+    if player_tick_actions.pressed(PlayerAction::Attack) {
+        player_flippable.flip_x = player_xform.translation.x > player_aim_target.x;
     }
     if player.can_jump {
         player.can_jump = false;
         player_grounded.0 = false;
-        player_vel.linvel.y = 200.;
+        player_vel.linvel.y = player_jump_vel.0;
+    }
+}
+
+fn teleport(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let &[x, y] = &console::parse_args::<f32>(args)?[..] else {
+        return Err(String::from("usage: tp <x> <y>"));
+    };
+    let (mut player_xform, mut player_vel, mut player_kcc) = world
+        .query_filtered::<(
+            &mut Transform,
+            &mut Velocity,
+            &mut KinematicCharacterController,
+        ), With<Player>>()
+        .get_single_mut(world)
+        .map_err(|_| String::from("no player"))?;
+
+    player_xform.translation.x = x;
+    player_xform.translation.y = y;
+    player_vel.linvel = Vec2::ZERO;
+    player_kcc.translation = None;
+    Ok(String::new())
+}
+
+fn toggle_god_mode(world: &mut World, _: &[&str]) -> Result<String, String> {
+    let player_id = world
+        .query_filtered::<Entity, With<Player>>()
+        .get_single(world)
+        .map_err(|_| String::from("no player"))?;
+    let mut player = world.entity_mut(player_id);
+
+    if player.take::<Invulnerable>().is_some() {
+        Ok(String::from("god mode off"))
+    } else {
+        player.insert(Invulnerable);
+        Ok(String::from("god mode on"))
+    }
+}
+
+fn toggle_noclip(world: &mut World, _: &[&str]) -> Result<String, String> {
+    let (mut player, mut player_kcc, mut player_net_dir, mut player_vel) = world
+        .query::<(
+            &mut Player,
+            &mut KinematicCharacterController,
+            &mut NetDirection,
+            &mut Velocity,
+        )>()
+        .get_single_mut(world)
+        .map_err(|_| String::from("no player"))?;
+
+    player.noclip = !player.noclip;
+    player_kcc
+        .filter_flags
+        .set(QueryFilterFlags::EXCLUDE_FIXED, player.noclip);
+    player_net_dir.y = if player.noclip { 0 } else { -1 };
+    player_vel.linvel.y = 0.;
+    Ok(format!(
+        "noclip {}",
+        if player.noclip { "on" } else { "off" }
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*, crate::simulation::Simulation, bevy::time::TimeUpdateStrategy, physics::TIMESTEP,
+    };
+
+    const TILE_HEIGHT: f32 = 16.;
+
+    #[derive(Resource, Default)]
+    struct JumpEdges(u32);
+
+    fn count_jump_edges(mut edges: ResMut<JumpEdges>, player_qry: Query<&TickActions>) {
+        for player_tick_actions in player_qry.iter() {
+            if player_tick_actions.just_pressed(PlayerAction::Jump) {
+                edges.0 += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn player_falls_onto_floor() {
+        let mut sim = Simulation::new();
+        let spawn_y = sim.player::<Transform>().translation.y;
+        sim.settle(256);
+
+        assert!(sim.player::<Grounded>().0);
+        assert!(sim.player::<Transform>().translation.y < spawn_y);
+    }
+
+    #[test]
+    fn walking_moves_player_horizontally() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let start_x = sim.player::<Transform>().translation.x;
+
+        sim.step_n(32, &[PlayerAction::MoveRight]);
+        let right_x = sim.player::<Transform>().translation.x;
+        assert!(right_x > start_x);
+        assert!(!sim.player::<TextureAtlasSprite>().flip_x);
+
+        sim.step_n(64, &[PlayerAction::MoveLeft]);
+        assert!(sim.player::<Transform>().translation.x < right_x);
+    }
+
+    #[test]
+    fn jump_clears_three_tiles() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let floor_y = sim.player::<Transform>().translation.y;
+
+        sim.step(&[PlayerAction::Jump]);
+        let mut peak_y = floor_y;
+        for _ in 0..96 {
+            sim.step(&[]);
+            peak_y = peak_y.max(sim.player::<Transform>().translation.y);
+        }
+
+        assert!(peak_y - floor_y >= 3. * TILE_HEIGHT);
+        assert!(sim.player::<Grounded>().0);
+    }
+
+    #[test]
+    fn noclip_flies_vertically_through_tiles() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let floor_y = sim.player::<Transform>().translation.y;
+        assert_eq!(
+            toggle_noclip(&mut sim.app.world, &[]).as_deref(),
+            Ok("noclip on")
+        );
+
+        sim.step_n(64, &[PlayerAction::MoveDown]);
+        let below_y = sim.player::<Transform>().translation.y;
+        assert!(below_y < floor_y - 3. * TILE_HEIGHT);
+        sim.step_n(16, &[]);
+        assert_eq!(sim.player::<Transform>().translation.y, below_y);
+
+        sim.step_n(128, &[PlayerAction::MoveUp]);
+        assert!(sim.player::<Transform>().translation.y > floor_y);
+    }
+
+    #[test]
+    fn presses_fire_once_per_tick_whatever_the_frame_rate() {
+        let mut sim = Simulation::with(|app| {
+            app.init_resource::<JumpEdges>()
+                .add_systems(FixedUpdate, count_jump_edges.after(latch_player_actions));
+        });
+        let step = |sim: &mut Simulation, ticks: u32, actions: &[PlayerAction]| {
+            sim.app
+                .world
+                .insert_resource(TimeUpdateStrategy::ManualDuration(TIMESTEP * ticks));
+            sim.step(actions);
+            sim.app.world.resource::<JumpEdges>().0
+        };
+
+        assert_eq!(step(&mut sim, 3, &[PlayerAction::Jump]), 1);
+        assert_eq!(step(&mut sim, 1, &[PlayerAction::Jump]), 1);
+        assert_eq!(step(&mut sim, 1, &[]), 1);
+        assert_eq!(step(&mut sim, 0, &[PlayerAction::Jump]), 1);
+        assert_eq!(step(&mut sim, 1, &[]), 2);
     }
 }
@@ -0,0 +1,353 @@
+use {
+    super::{
+        game_state::{self, GameState},
+        health::DamageEvent,
+        lighting::LightSource,
+        physics::{self, ACTOR_GROUP, TILE_GROUP},
+        status_effect::{ApplyStatus, StatusEffect},
+        time_control::TimeScale,
+        weapon::WeaponFired,
+    },
+    bevy::prelude::*,
+    bevy_rapier2d::prelude::*,
+    serde::Deserialize,
+};
+
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ProjectileHit>()
+            .add_systems(game_state::END_RUN, game_state::despawn_with::<Projectile>)
+            .add_systems(
+                FixedUpdate,
+                (
+                    fire_projectiles,
+                    move_projectiles,
+                    damage_projectile_targets,
+                )
+                    .chain()
+                    .after(physics::apply_forces)
+                    .distributive_run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+const MAX_CASTS_PER_TICK: usize = 4;
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct ProjectileDef {
+    pub sprite: String,
+    pub speed: f32,
+    #[serde(default)]
+    pub gravity: f32,
+    pub lifetime: f32,
+    #[serde(default)]
+    pub pierce: u32,
+    #[serde(default)]
+    pub bounces: u32,
+    #[serde(default)]
+    pub light: Option<LightSource>,
+    #[serde(default)]
+    pub status: Option<StatusEffect>,
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    pub owner: Option<Entity>,
+    pub damage: u32,
+    pub knockback: f32,
+    pub gravity: f32,
+    pub lifetime: Timer,
+    pub pierce: u32,
+    pub bounces: u32,
+    pub status: Option<StatusEffect>,
+    hits: Vec<Entity>,
+}
+
+impl Projectile {
+    pub fn new(def: &ProjectileDef, owner: Option<Entity>, damage: u32, knockback: f32) -> Self {
+        Self {
+            owner,
+            damage,
+            knockback,
+            gravity: def.gravity,
+            lifetime: Timer::from_seconds(def.lifetime, TimerMode::Once),
+            pierce: def.pierce,
+            bounces: def.bounces,
+            status: def.status,
+            hits: Vec::new(),
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct ProjectileHit {
+    pub target: Entity,
+    pub damage: u32,
+    pub knockback: Vec2,
+    pub status: Option<StatusEffect>,
+}
+
+pub fn spawn_projectile(
+    cmds: &mut Commands,
+    tex: Handle<Image>,
+    projectile: Projectile,
+    pos: Vec2,
+    vel: Vec2,
+) -> Entity {
+    cmds.spawn((
+        Name::new("Projectile"),
+        projectile,
+        SpriteBundle {
+            texture: tex,
+            transform: Transform::from_translation(pos.extend(1.))
+                .with_rotation(Quat::from_rotation_z(vel.y.atan2(vel.x))),
+            ..default()
+        },
+        Velocity::linear(vel),
+    ))
+    .id()
+}
+
+fn fire_projectiles(
+    mut cmds: Commands,
+    asset_server: Res<AssetServer>,
+    mut weapon_fired_evr: EventReader<WeaponFired>,
+) {
+    for weapon_fired in weapon_fired_evr.read() {
+        let projectile_id = spawn_projectile(
+            &mut cmds,
+            asset_server.load(&weapon_fired.projectile.sprite),
+            Projectile::new(
+                &weapon_fired.projectile,
+                Some(weapon_fired.owner),
+                weapon_fired.damage,
+                weapon_fired.knockback,
+            ),
+            weapon_fired.origin,
+            weapon_fired.direction * weapon_fired.projectile.speed,
+        );
+        if let Some(light) = weapon_fired.projectile.light {
+            cmds.entity(projectile_id).insert(light);
+        }
+    }
+}
+
+fn move_projectiles(
+    mut cmds: Commands,
+    time: Res<Time<Fixed>>,
+    rapier_ctx: Res<RapierContext>,
+    mut projectile_hit_evw: EventWriter<ProjectileHit>,
+    mut projectile_qry: Query<(
+        Entity,
+        &mut Projectile,
+        &mut Velocity,
+        &mut Transform,
+        Option<&TimeScale>,
+    )>,
+    groups_qry: Query<&CollisionGroups>,
+) {
+    'projectiles: for (projectile_id, mut projectile, mut vel, mut projectile_xform, time_scale) in
+        projectile_qry.iter_mut()
+    {
+        let scale = time_scale.map_or(1., |time_scale| time_scale.0);
+        let dt = time.timestep().as_secs_f32() * scale;
+        projectile.lifetime.tick(time.timestep().mul_f32(scale));
+        if projectile.lifetime.finished() {
+            cmds.entity(projectile_id).despawn_recursive();
+            continue;
+        }
+        vel.linvel.y -= projectile.gravity * dt;
+
+        let mut pos = projectile_xform.translation.truncate();
+        let mut remaining = dt;
+        for _ in 0..MAX_CASTS_PER_TICK {
+            let not_hit = |entity: Entity| !projectile.hits.contains(&entity);
+            let mut filter = QueryFilter::new()
+                .exclude_sensors()
+                .groups(CollisionGroups::new(Group::ALL, TILE_GROUP | ACTOR_GROUP))
+                .predicate(&not_hit);
+            if let Some(owner_id) = projectile.owner {
+                filter = filter.exclude_collider(owner_id);
+            }
+            let Some((hit_id, hit)) =
+                rapier_ctx.cast_ray_and_get_normal(pos, vel.linvel, remaining, true, filter)
+            else {
+                pos += vel.linvel * remaining;
+                break;
+            };
+            pos += vel.linvel * hit.toi;
+            remaining -= hit.toi;
+
+            let hit_terrain = groups_qry
+                .get(hit_id)
+                .is_ok_and(|groups| !groups.memberships.contains(ACTOR_GROUP));
+            if hit_terrain {
+                if projectile.bounces == 0 {
+                    cmds.entity(projectile_id).despawn_recursive();
+                    continue 'projectiles;
+                }
+                projectile.bounces -= 1;
+                vel.linvel -= 2. * vel.linvel.dot(hit.normal) * hit.normal;
+                pos += hit.normal * 0.01;
+            } else {
+                projectile.hits.push(hit_id);
+                projectile_hit_evw.send(ProjectileHit {
+                    target: hit_id,
+                    damage: projectile.damage,
+                    knockback: vel.linvel.normalize_or_zero() * projectile.knockback,
+                    status: projectile.status,
+                });
+                if projectile.pierce == 0 {
+                    cmds.entity(projectile_id).despawn_recursive();
+                    continue 'projectiles;
+                }
+                projectile.pierce -= 1;
+            }
+        }
+
+        projectile_xform.translation = pos.extend(projectile_xform.translation.z);
+        projectile_xform.rotation = Quat::from_rotation_z(vel.linvel.y.atan2(vel.linvel.x));
+    }
+}
+
+fn damage_projectile_targets(
+    mut projectile_hit_evr: EventReader<ProjectileHit>,
+    mut damage_evw: EventWriter<DamageEvent>,
+    mut apply_status_evw: EventWriter<ApplyStatus>,
+) {
+    for projectile_hit in projectile_hit_evr.read() {
+        damage_evw.send(DamageEvent {
+            target: projectile_hit.target,
+            amount: projectile_hit.damage,
+            knockback: projectile_hit.knockback,
+        });
+        if let Some(effect) = projectile_hit.status {
+            apply_status_evw.send(ApplyStatus {
+                target: projectile_hit.target,
+                effect,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            door::{self, DoorDef},
+            health::Health,
+            simulation::Simulation,
+        },
+        bevy::ecs::system::RunSystemOnce,
+    };
+
+    fn arrow(pierce: u32, bounces: u32) -> ProjectileDef {
+        ProjectileDef {
+            sprite: String::new(),
+            speed: 200.,
+            gravity: 0.,
+            lifetime: 2.,
+            pierce,
+            bounces,
+            light: None,
+            status: None,
+        }
+    }
+
+    fn spawn(sim: &mut Simulation, projectile: Projectile, pos: Vec2, vel: Vec2) -> Entity {
+        sim.app
+            .world
+            .spawn((
+                projectile,
+                TransformBundle::from_transform(Transform::from_translation(pos.extend(1.))),
+                Velocity::linear(vel),
+            ))
+            .id()
+    }
+
+    fn spawn_target(sim: &mut Simulation, pos: Vec2) -> Entity {
+        sim.app
+            .world
+            .spawn((
+                Health::new(5),
+                Collider::cuboid(4., 4.),
+                CollisionGroups::new(ACTOR_GROUP, Group::ALL),
+                TransformBundle::from_transform(Transform::from_translation(pos.extend(0.))),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn piercing_projectile_damages_each_target_once() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let near = spawn_target(&mut sim, Vec2::new(-40., -200.));
+        let far = spawn_target(&mut sim, Vec2::new(40., -200.));
+        let projectile = spawn(
+            &mut sim,
+            Projectile::new(&arrow(1, 0), None, 2, 0.),
+            Vec2::new(-100., -200.),
+            Vec2::new(200., 0.),
+        );
+
+        sim.step_n(64, &[]);
+
+        assert_eq!(sim.app.world.get::<Health>(near).unwrap().current, 3);
+        assert_eq!(sim.app.world.get::<Health>(far).unwrap().current, 3);
+        assert!(sim.app.world.get_entity(projectile).is_none());
+    }
+
+    #[test]
+    fn projectile_bounces_off_tiles() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let projectile = spawn(
+            &mut sim,
+            Projectile::new(&arrow(0, 1), None, 1, 0.),
+            Vec2::new(-100., -200.),
+            Vec2::new(0., -200.),
+        );
+
+        sim.step_n(32, &[]);
+        assert!(sim.app.world.get::<Velocity>(projectile).unwrap().linvel.y > 0.);
+
+        sim.step_n(128, &[]);
+        assert!(sim.app.world.get_entity(projectile).is_none());
+    }
+
+    #[test]
+    fn closed_doors_stop_projectiles_like_tiles() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        sim.app.world.run_system_once(|mut cmds: Commands| {
+            door::spawn_door(
+                &mut cmds,
+                Handle::default(),
+                &DoorDef {
+                    id: String::from("gate"),
+                    room: [0, 0],
+                    pos: [0., -200.],
+                    lock: default(),
+                },
+                false,
+            );
+        });
+        let behind = spawn_target(&mut sim, Vec2::new(40., -200.));
+        let projectile = spawn(
+            &mut sim,
+            Projectile::new(&arrow(1, 1), None, 2, 0.),
+            Vec2::new(-100., -200.),
+            Vec2::new(200., 0.),
+        );
+
+        sim.step_n(40, &[]);
+        assert!(sim.app.world.get::<Velocity>(projectile).unwrap().linvel.x < 0.);
+
+        sim.step_n(128, &[]);
+        assert!(sim.app.world.get_entity(projectile).is_none());
+        assert_eq!(sim.app.world.get::<Health>(behind).unwrap().current, 5);
+    }
+}
@@ -0,0 +1,517 @@
+use {
+    super::{
+        aim::{AimDevices, AimSource, AimTarget},
+        cli,
+        game_state::{self, GameState},
+        physics,
+        player::{self, Player, PlayerAction, TickActions},
+        rng::{PinnedSeed, RunSeed},
+    },
+    bevy::{app::AppExit, prelude::*},
+    leafwing_input_manager::prelude::*,
+    std::{fmt, fs, io, path::PathBuf},
+};
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(path) = cli::arg_value("--replay") {
+            match fs::read(&path)
+                .map_err(ReplayError::Io)
+                .and_then(|bytes| Replay::decode(&bytes))
+            {
+                Ok(replay) => {
+                    app.insert_resource(RunSeed(replay.seed))
+                        .insert_resource(PinnedSeed)
+                        .insert_resource(ReplayMode::Replaying { replay, tick: 0 });
+                }
+                Err(err) => error!("failed to load replay {path}: {err}"),
+            }
+        } else if let Some(path) = cli::arg_value("--record") {
+            app.insert_resource(ReplayMode::Recording {
+                path: Some(PathBuf::from(path)),
+                replay: Replay::default(),
+            });
+        }
+
+        app.add_systems(
+            Startup,
+            skip_main_menu.run_if(resource_exists::<ReplayMode>()),
+        )
+        .add_systems(
+            game_state::START_RUN,
+            (
+                start_recording.run_if(recording),
+                detach_player_input
+                    .after(player::spawn_player)
+                    .run_if(replaying),
+            ),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                feed_replay_input
+                    .after(physics::process_collisions)
+                    .before(player::latch_player_actions)
+                    .run_if(replaying),
+                record_player_input
+                    .after(player::latch_player_actions)
+                    .before(player::discrete_player_input)
+                    .run_if(recording),
+            )
+                .distributive_run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(Last, save_recording.run_if(recording));
+    }
+}
+
+const MAGIC: &[u8; 4] = b"DJRP";
+const VERSION: u8 = 5;
+const CHECKSUM_INTERVAL: u32 = 64;
+
+const MOVE_LEFT_BIT: u8 = 1 << 0;
+const MOVE_RIGHT_BIT: u8 = 1 << 1;
+const JUMP_BIT: u8 = 1 << 2;
+const ATTACK_BIT: u8 = 1 << 3;
+const CYCLE_EQUIPMENT_BIT: u8 = 1 << 4;
+const INTERACT_BIT: u8 = 1 << 5;
+const MOVE_UP_BIT: u8 = 1 << 6;
+const MOVE_DOWN_BIT: u8 = 1 << 7;
+
+const ACTION_BITS: [(PlayerAction, u8); 8] = [
+    (PlayerAction::MoveLeft, MOVE_LEFT_BIT),
+    (PlayerAction::MoveRight, MOVE_RIGHT_BIT),
+    (PlayerAction::Jump, JUMP_BIT),
+    (PlayerAction::Attack, ATTACK_BIT),
+    (PlayerAction::CycleEquipment, CYCLE_EQUIPMENT_BIT),
+    (PlayerAction::Interact, INTERACT_BIT),
+    (PlayerAction::MoveUp, MOVE_UP_BIT),
+    (PlayerAction::MoveDown, MOVE_DOWN_BIT),
+];
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::BadMagic => write!(f, "not a replay file"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported replay version {version}"),
+            Self::Truncated => write!(f, "replay file is truncated"),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub struct TickInput {
+    pub actions: u8,
+    pub aim: Vec2,
+}
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub inputs: Vec<TickInput>,
+    pub checksums: Vec<(u32, u64)>,
+}
+
+impl Replay {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut runs = Vec::<(TickInput, u16)>::new();
+        for &input in self.inputs.iter() {
+            match runs.last_mut() {
+                Some((run_input, run_len)) if *run_input == input && *run_len < u16::MAX => {
+                    *run_len += 1
+                }
+                _ => runs.push((input, 1)),
+            }
+        }
+
+        let mut bytes = Vec::with_capacity(21 + runs.len() * 11 + self.checksums.len() * 12);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+        for (input, len) in runs {
+            bytes.push(input.actions);
+            bytes.extend_from_slice(&input.aim.x.to_le_bytes());
+            bytes.extend_from_slice(&input.aim.y.to_le_bytes());
+            bytes.extend_from_slice(&len.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.checksums.len() as u32).to_le_bytes());
+        for (tick, checksum) in self.checksums.iter() {
+            bytes.extend_from_slice(&tick.to_le_bytes());
+            bytes.extend_from_slice(&checksum.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ReplayError> {
+        let mut reader = ByteReader(bytes);
+        if reader.take(4)? != MAGIC {
+            return Err(ReplayError::BadMagic);
+        }
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+
+        let mut replay = Self {
+            seed: u64::from_le_bytes(reader.array()?),
+            ..default()
+        };
+        for _ in 0..u32::from_le_bytes(reader.array()?) {
+            let input = TickInput {
+                actions: reader.take(1)?[0],
+                aim: Vec2::new(
+                    f32::from_le_bytes(reader.array()?),
+                    f32::from_le_bytes(reader.array()?),
+                ),
+            };
+            let len = u16::from_le_bytes(reader.array()?);
+            replay
+                .inputs
+                .extend(std::iter::repeat_n(input, len as usize));
+        }
+        for _ in 0..u32::from_le_bytes(reader.array()?) {
+            replay.checksums.push((
+                u32::from_le_bytes(reader.array()?),
+                u64::from_le_bytes(reader.array()?),
+            ));
+        }
+        Ok(replay)
+    }
+}
+
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ReplayError> {
+        if self.0.len() < len {
+            return Err(ReplayError::Truncated);
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ReplayError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+}
+
+#[derive(Resource)]
+pub enum ReplayMode {
+    Recording {
+        path: Option<PathBuf>,
+        replay: Replay,
+    },
+    Replaying {
+        replay: Replay,
+        tick: usize,
+    },
+}
+
+fn recording(mode: Option<Res<ReplayMode>>) -> bool {
+    matches!(mode.as_deref(), Some(ReplayMode::Recording { .. }))
+}
+
+fn replaying(mode: Option<Res<ReplayMode>>) -> bool {
+    matches!(mode.as_deref(), Some(ReplayMode::Replaying { .. }))
+}
+
+fn position_checksum(xform: &Transform) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for bits in [xform.translation.x.to_bits(), xform.translation.y.to_bits()] {
+        for byte in bits.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+fn skip_main_menu(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::Playing);
+}
+
+fn start_recording(mut mode: ResMut<ReplayMode>, seed: Res<RunSeed>) {
+    if let ReplayMode::Recording { replay, .. } = mode.as_mut() {
+        *replay = Replay {
+            seed: seed.0,
+            ..default()
+        };
+    }
+}
+
+fn detach_player_input(mut cmds: Commands, player_qry: Query<Entity, With<Player>>) {
+    for player_id in player_qry.iter() {
+        cmds.entity(player_id)
+            .remove::<(InputMap<PlayerAction>, AimSource, AimDevices)>();
+    }
+}
+
+fn record_player_input(
+    mut mode: ResMut<ReplayMode>,
+    player_qry: Query<(&TickActions, &AimTarget, &Transform)>,
+) {
+    let ReplayMode::Recording { replay, .. } = mode.as_mut() else {
+        return;
+    };
+    let Ok((player_tick_actions, player_aim_target, player_xform)) = player_qry.get_single() else {
+        return;
+    };
+
+    let tick = replay.inputs.len() as u32;
+    if tick % CHECKSUM_INTERVAL == 0 {
+        replay
+            .checksums
+            .push((tick, position_checksum(player_xform)));
+    }
+
+    let mut actions = 0;
+    for (action, bit) in ACTION_BITS {
+        if player_tick_actions.pressed(action) {
+            actions |= bit;
+        }
+    }
+    replay.inputs.push(TickInput {
+        actions,
+        aim: player_aim_target.0,
+    });
+}
+
+fn feed_replay_input(
+    mut mode: ResMut<ReplayMode>,
+    mut player_qry: Query<(&mut ActionState<PlayerAction>, &mut AimTarget, &Transform)>,
+) {
+    let ReplayMode::Replaying { replay, tick } = mode.as_mut() else {
+        return;
+    };
+    let Ok((mut player_actions, mut player_aim_target, player_xform)) = player_qry.get_single_mut()
+    else {
+        return;
+    };
+
+    if let Some(&(_, expected)) = replay
+        .checksums
+        .iter()
+        .find(|(checksum_tick, _)| *checksum_tick as usize == *tick)
+    {
+        if position_checksum(player_xform) != expected {
+            error!("replay desynced at tick {tick}");
+        }
+    }
+
+    let Some(&input) = replay.inputs.get(*tick) else {
+        if *tick == replay.inputs.len() {
+            info!("replay finished after {tick} ticks");
+            player_actions.release_all();
+            *tick += 1;
+        }
+        return;
+    };
+    for (action, bit) in ACTION_BITS {
+        if input.actions & bit != 0 {
+            player_actions.press(action);
+        } else {
+            player_actions.release(action);
+        }
+    }
+    player_aim_target.0 = input.aim;
+    *tick += 1;
+}
+
+fn save_recording(mut exit_evr: EventReader<AppExit>, mode: Res<ReplayMode>) {
+    if exit_evr.read().next().is_none() {
+        return;
+    }
+    let ReplayMode::Recording {
+        path: Some(path),
+        replay,
+    } = mode.as_ref()
+    else {
+        return;
+    };
+    match fs::write(path, replay.encode()) {
+        Ok(()) => info!("saved replay to {}", path.display()),
+        Err(err) => error!("failed to save replay to {}: {err}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            inventory::{Inventory, ItemCatalog, ItemCatalogHandle, ItemStack},
+            projectile::Projectile,
+            simulation::Simulation,
+            weapon::WeaponDef,
+        },
+        bevy_rapier2d::prelude::Velocity,
+        std::fs,
+    };
+
+    fn equip_bow(sim: &mut Simulation) -> Handle<WeaponDef> {
+        let catalog = ron::de::from_str::<ItemCatalog>(
+            &fs::read_to_string("assets/data/base.items.ron").unwrap(),
+        )
+        .unwrap();
+        let catalog_handle = sim
+            .app
+            .world
+            .resource_mut::<Assets<ItemCatalog>>()
+            .add(catalog);
+        sim.app
+            .world
+            .insert_resource(ItemCatalogHandle(catalog_handle));
+
+        let bow_def = ron::de::from_str::<WeaponDef>(
+            &fs::read_to_string("assets/weapons/bow.weapon.ron").unwrap(),
+        )
+        .unwrap();
+        let bow = sim
+            .app
+            .world
+            .resource::<AssetServer>()
+            .load::<WeaponDef>("weapons/bow.weapon.ron");
+        sim.app
+            .world
+            .resource_mut::<Assets<WeaponDef>>()
+            .insert(&bow, bow_def);
+
+        let mut inventory = sim.player_mut::<Inventory>();
+        inventory.slots[1] = Some(ItemStack::new("bow", 1));
+        inventory.equipped = Some(1);
+        bow
+    }
+
+    fn arrow_velocities(sim: &mut Simulation) -> Vec<Vec2> {
+        sim.app
+            .world
+            .query_filtered::<&Velocity, With<Projectile>>()
+            .iter(&sim.app.world)
+            .map(|vel| vel.linvel)
+            .collect()
+    }
+
+    #[test]
+    fn replay_round_trips_through_bytes() {
+        let replay = Replay {
+            seed: 0xdead_beef,
+            inputs: [
+                vec![TickInput::default(); 300],
+                vec![TickInput {
+                    actions: MOVE_RIGHT_BIT | JUMP_BIT,
+                    aim: Vec2::new(-12.5, 40.),
+                }],
+                vec![
+                    TickInput {
+                        actions: MOVE_LEFT_BIT,
+                        aim: Vec2::new(3., -7.25),
+                    };
+                    70_000
+                ],
+            ]
+            .concat(),
+            checksums: vec![(0, 1), (64, 2)],
+        };
+        assert_eq!(Replay::decode(&replay.encode()).unwrap(), replay);
+
+        let mut other_version = replay.encode();
+        for version in [1, VERSION - 1, VERSION + 1] {
+            other_version[MAGIC.len()] = version;
+            assert!(matches!(
+                Replay::decode(&other_version),
+                Err(ReplayError::UnsupportedVersion(found)) if found == version
+            ));
+        }
+        assert!(matches!(
+            Replay::decode(b"nope"),
+            Err(ReplayError::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn replayed_run_matches_recording() {
+        let mut recording_sim = Simulation::with(|app| {
+            app.insert_resource(ReplayMode::Recording {
+                path: None,
+                replay: Replay::default(),
+            });
+        });
+        recording_sim.step_n(40, &[]);
+        recording_sim.step_n(20, &[PlayerAction::MoveRight]);
+        recording_sim.step(&[PlayerAction::MoveRight, PlayerAction::Jump]);
+        recording_sim.step_n(30, &[PlayerAction::MoveLeft]);
+        recording_sim.step(&[PlayerAction::CycleEquipment]);
+        recording_sim.step(&[PlayerAction::Interact]);
+        recording_sim.step_n(40, &[]);
+        let ReplayMode::Recording { replay, .. } = recording_sim.app.world.resource::<ReplayMode>()
+        else {
+            unreachable!();
+        };
+        let replay = replay.clone();
+        for bit in [JUMP_BIT, CYCLE_EQUIPMENT_BIT, INTERACT_BIT] {
+            assert!(replay.inputs.iter().any(|input| input.actions & bit != 0));
+        }
+        let ticks = replay.inputs.len();
+
+        let mut replaying_sim = Simulation::with(|app| {
+            app.insert_resource(ReplayMode::Replaying { replay, tick: 0 });
+        });
+        replaying_sim.step_n(ticks, &[]);
+
+        assert_eq!(
+            replaying_sim.player::<Transform>().translation,
+            recording_sim.player::<Transform>().translation
+        );
+    }
+
+    #[test]
+    fn replayed_bow_shots_follow_the_recorded_aim() {
+        let mut recording_sim = Simulation::with(|app| {
+            app.insert_resource(ReplayMode::Recording {
+                path: None,
+                replay: Replay::default(),
+            });
+        });
+        let _bow = equip_bow(&mut recording_sim);
+        recording_sim.step_n(40, &[]);
+        let aim = recording_sim.player::<Transform>().translation.truncate() + Vec2::new(-30., 40.);
+        recording_sim.player_mut::<AimTarget>().0 = aim;
+        recording_sim.step(&[PlayerAction::Attack]);
+        recording_sim.step_n(3, &[]);
+        let recorded_arrows = arrow_velocities(&mut recording_sim);
+        assert_eq!(recorded_arrows.len(), 1);
+        assert!(recorded_arrows[0].x < 0. && recorded_arrows[0].y > 0.);
+        assert!(recording_sim.player::<TextureAtlasSprite>().flip_x);
+
+        let ReplayMode::Recording { replay, .. } = recording_sim.app.world.resource::<ReplayMode>()
+        else {
+            unreachable!();
+        };
+        let replay = Replay::decode(&replay.encode()).unwrap();
+        let ticks = replay.inputs.len();
+        let mut replaying_sim = Simulation::with(|app| {
+            app.insert_resource(ReplayMode::Replaying { replay, tick: 0 });
+        });
+        let _bow = equip_bow(&mut replaying_sim);
+        replaying_sim.step_n(ticks, &[]);
+
+        assert_eq!(replaying_sim.player::<AimTarget>().0, aim);
+        assert_eq!(arrow_velocities(&mut replaying_sim), recorded_arrows);
+        assert_eq!(
+            replaying_sim.player::<Transform>().translation,
+            recording_sim.player::<Transform>().translation
+        );
+    }
+}
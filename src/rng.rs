@@ -0,0 +1,63 @@
+use {
+    super::cli,
+    bevy::prelude::*,
+    std::{
+        ops::Range,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        match cli::arg_value("--seed").and_then(|seed| seed.parse().ok()) {
+            Some(seed) => app
+                .insert_resource(RunSeed(seed))
+                .insert_resource(PinnedSeed),
+            None => app.insert_resource(RunSeed(clock_seed())),
+        };
+    }
+}
+
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq, Deref, DerefMut)]
+pub struct RunSeed(pub u64);
+
+impl RunSeed {
+    pub fn reroll(&mut self) {
+        self.0 = SeededRng::new(self.0 ^ clock_seed()).next_u64();
+    }
+}
+
+#[derive(Resource)]
+pub struct PinnedSeed;
+
+fn clock_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_nanos() as u64)
+}
+
+#[derive(Clone, Debug)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn range(&mut self, range: Range<u32>) -> u32 {
+        let span = range.end.saturating_sub(range.start).max(1);
+        range.start + (self.next_u64() % span as u64) as u32
+    }
+}
@@ -0,0 +1,85 @@
+use {
+    bevy::{
+        asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+        prelude::*,
+        utils::BoxedFuture,
+    },
+    serde::de::DeserializeOwned,
+    std::{error::Error, fmt, io, marker::PhantomData},
+};
+
+pub struct RonAssetPlugin<A> {
+    extensions: &'static [&'static str],
+    _marker: PhantomData<A>,
+}
+
+impl<A> RonAssetPlugin<A> {
+    pub fn new(extensions: &'static [&'static str]) -> Self {
+        Self {
+            extensions,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A: Asset + DeserializeOwned> Plugin for RonAssetPlugin<A> {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<A>()
+            .register_asset_loader(RonAssetLoader::<A> {
+                extensions: self.extensions,
+                _marker: PhantomData,
+            });
+    }
+}
+
+pub fn one() -> u32 {
+    1
+}
+
+struct RonAssetLoader<A> {
+    extensions: &'static [&'static str],
+    _marker: PhantomData<A>,
+}
+
+#[derive(Debug)]
+pub enum RonAssetError {
+    Io(io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for RonAssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Ron(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for RonAssetError {}
+
+impl<A: Asset + DeserializeOwned> AssetLoader for RonAssetLoader<A> {
+    type Asset = A;
+    type Settings = ();
+    type Error = RonAssetError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader
+                .read_to_end(&mut bytes)
+                .await
+                .map_err(RonAssetError::Io)?;
+            ron::de::from_bytes(&bytes).map_err(RonAssetError::Ron)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
@@ -0,0 +1,603 @@
+use {
+    super::{
+        console::{self, AddConsoleCommand},
+        floor::{self, FloorLayout, FloorState, RoomState},
+        game_state::GameState,
+        health::Health,
+        inventory::{Inventory, ItemStack},
+        player::{Player, PLAYER_INVENTORY_SLOTS},
+        rng::RunSeed,
+        stats::Experience,
+        tile::{TileWorld, TILEMAP_SIZE},
+    },
+    bevy::prelude::*,
+    bevy_ecs_tilemap::prelude::*,
+    bevy_rapier2d::prelude::*,
+    serde::{Deserialize, Serialize},
+    std::{
+        fmt, fs, io,
+        path::PathBuf,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveSlots>()
+            .add_event::<SaveGame>()
+            .add_event::<LoadGame>()
+            .add_systems(
+                Update,
+                (save_game, load_game)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_console_command("save", "save <slot>: save the run", save_command)
+            .add_console_command("load", "load <slot>: load a saved run", load_command);
+
+        #[cfg(feature = "dev")]
+        app.add_systems(
+            Update,
+            quick_save_and_load
+                .before(save_game)
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+pub const SAVE_VERSION: u32 = 3;
+pub const SLOT_COUNT: u8 = 3;
+
+#[derive(Event)]
+pub struct SaveGame {
+    pub slot: u8,
+}
+
+#[derive(Event)]
+pub struct LoadGame {
+    pub slot: u8,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Parse(err) => write!(f, "{err}"),
+            Self::Serialize(err) => write!(f, "{err}"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported save version {version}"),
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for SaveError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl From<ron::Error> for SaveError {
+    fn from(err: ron::Error) -> Self {
+        Self::Serialize(err)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SaveData {
+    pub version: u32,
+    pub saved_at: u64,
+    pub seed: u64,
+    pub state: GameState,
+    pub tilemap: SavedTilemap,
+    pub player: SavedPlayer,
+    #[serde(default)]
+    pub rooms: Vec<SavedRoom>,
+    #[serde(default)]
+    pub edited_tilemaps: Vec<SavedTilemap>,
+    #[serde(default)]
+    pub opened_doors: Vec<String>,
+    #[serde(default)]
+    pub explored: Vec<[i32; 2]>,
+    #[serde(default)]
+    pub depth: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SavedTilemap {
+    #[serde(default)]
+    pub room: [i32; 2],
+    pub size: [u32; 2],
+    pub tiles: Vec<SavedTile>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SavedTile {
+    pub x: u32,
+    pub y: u32,
+    pub texture: u32,
+}
+
+impl SavedTilemap {
+    fn new(room: IVec2, tiles: impl Iterator<Item = (IVec2, TileTextureIndex)>) -> Self {
+        let origin = floor::room_tile_origin(room);
+        Self {
+            room: room.to_array(),
+            size: [TILEMAP_SIZE.x, TILEMAP_SIZE.y],
+            tiles: tiles
+                .map(|(tile, tex_idx)| SavedTile {
+                    x: (tile - origin).x as u32,
+                    y: (tile - origin).y as u32,
+                    texture: tex_idx.0,
+                })
+                .collect(),
+        }
+    }
+
+    fn tiles(&self) -> (IVec2, Vec<(IVec2, TileTextureIndex)>) {
+        let room = IVec2::from_array(self.room);
+        let origin = floor::room_tile_origin(room);
+        let [width, height] = self.size;
+        let tiles = self
+            .tiles
+            .iter()
+            .filter(|tile| tile.x < width && tile.y < height)
+            .map(|tile| {
+                (
+                    origin + IVec2::new(tile.x as i32, tile.y as i32),
+                    TileTextureIndex(tile.texture),
+                )
+            })
+            .collect();
+        (room, tiles)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SavedRoom {
+    pub room: [i32; 2],
+    pub state: RoomState,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SavedPlayer {
+    pub translation: [f32; 3],
+    pub linvel: [f32; 2],
+    pub health: u32,
+    pub max_health: u32,
+    pub inventory: Vec<Option<ItemStack>>,
+    pub equipped: Option<usize>,
+    #[serde(default)]
+    pub experience: Experience,
+}
+
+#[derive(Deserialize)]
+struct SaveDataV1 {
+    saved_at: u64,
+    seed: u64,
+    state: GameState,
+    tilemap: SavedTilemap,
+    player: SavedPlayerV1,
+}
+
+#[derive(Deserialize)]
+struct SavedPlayerV1 {
+    translation: [f32; 3],
+    linvel: [f32; 2],
+    health: u32,
+    max_health: u32,
+}
+
+impl From<SaveDataV1> for SaveData {
+    fn from(save: SaveDataV1) -> Self {
+        let mut inventory = vec![None; PLAYER_INVENTORY_SLOTS];
+        inventory[0] = Some(ItemStack::new("sword", 1));
+        Self {
+            version: SAVE_VERSION,
+            saved_at: save.saved_at,
+            seed: save.seed,
+            state: save.state,
+            tilemap: save.tilemap,
+            player: SavedPlayer {
+                translation: save.player.translation,
+                linvel: save.player.linvel,
+                health: save.player.health,
+                max_health: save.player.max_health,
+                inventory,
+                equipped: Some(0),
+                experience: Experience::default(),
+            },
+            rooms: Vec::new(),
+            edited_tilemaps: Vec::new(),
+            opened_doors: Vec::new(),
+            explored: Vec::new(),
+            depth: 0,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+    saved_at: u64,
+}
+
+impl SaveData {
+    pub fn from_ron(text: &str) -> Result<Self, SaveError> {
+        let header = ron::from_str::<SaveHeader>(text)?;
+        match header.version {
+            1 => Ok(ron::from_str::<SaveDataV1>(text)?.into()),
+            2 | SAVE_VERSION => Ok(Self {
+                version: SAVE_VERSION,
+                ..ron::from_str(text)?
+            }),
+            version => Err(SaveError::UnsupportedVersion(version)),
+        }
+    }
+
+    pub fn to_ron(&self) -> Result<String, SaveError> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+}
+
+#[derive(Resource)]
+pub struct SaveSlots {
+    pub dir: PathBuf,
+}
+
+impl Default for SaveSlots {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("saves"),
+        }
+    }
+}
+
+impl SaveSlots {
+    pub fn path(&self, slot: u8) -> PathBuf {
+        self.dir.join(format!("slot_{slot}.ron"))
+    }
+
+    pub fn read(&self, slot: u8) -> Result<SaveData, SaveError> {
+        SaveData::from_ron(&fs::read_to_string(self.path(slot))?)
+    }
+
+    pub fn write(&self, slot: u8, save: &SaveData) -> Result<(), SaveError> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(slot), save.to_ron()?)?;
+        Ok(())
+    }
+
+    pub fn latest(&self) -> Option<u8> {
+        (0..SLOT_COUNT)
+            .filter_map(|slot| {
+                let text = fs::read_to_string(self.path(slot)).ok()?;
+                let header = ron::from_str::<SaveHeader>(&text).ok()?;
+                Some((slot, header.saved_at))
+            })
+            .max_by_key(|&(_, saved_at)| saved_at)
+            .map(|(slot, _)| slot)
+    }
+}
+
+#[cfg(feature = "dev")]
+fn quick_save_and_load(
+    keys: Res<Input<KeyCode>>,
+    slots: Res<SaveSlots>,
+    mut save_evw: EventWriter<SaveGame>,
+    mut load_evw: EventWriter<LoadGame>,
+) {
+    if keys.just_pressed(KeyCode::F5) {
+        save_evw.send(SaveGame { slot: 0 });
+    }
+    if keys.just_pressed(KeyCode::F9) {
+        if let Some(slot) = slots.latest() {
+            load_evw.send(LoadGame { slot });
+        }
+    }
+}
+
+fn save_game(
+    mut save_evr: EventReader<SaveGame>,
+    slots: Res<SaveSlots>,
+    seed: Res<RunSeed>,
+    state: Res<State<GameState>>,
+    layout: Res<FloorLayout>,
+    floor_state: Res<FloorState>,
+    tile_world: Res<TileWorld>,
+    player_qry: Query<(&Transform, &Velocity, &Health, &Inventory, &Experience), With<Player>>,
+) {
+    for save_ev in save_evr.read() {
+        let Ok((player_xform, player_vel, player_health, player_inventory, player_experience)) =
+            player_qry.get_single()
+        else {
+            warn!("nothing to save");
+            continue;
+        };
+        let current = floor_state.current;
+        let mut rooms = floor_state
+            .rooms
+            .iter()
+            .map(|(room, &state)| SavedRoom {
+                room: room.to_array(),
+                state,
+            })
+            .collect::<Vec<_>>();
+        rooms.sort_by_key(|saved_room| saved_room.room);
+        let mut edited_tilemaps = floor_state
+            .snapshots
+            .iter()
+            .map(|(&room, tiles)| SavedTilemap::new(room, tiles.iter().copied()))
+            .chain(
+                floor_state
+                    .generated
+                    .iter()
+                    .filter(|&&room| room != current)
+                    .filter_map(|&room| {
+                        let tiles = floor::room_snapshot(&layout, &tile_world, room)?;
+                        Some(SavedTilemap::new(room, tiles.into_iter()))
+                    }),
+            )
+            .collect::<Vec<_>>();
+        edited_tilemaps.sort_by_key(|tilemap| tilemap.room);
+        let mut opened_doors = floor_state.opened_doors.iter().cloned().collect::<Vec<_>>();
+        opened_doors.sort_unstable();
+        let mut explored = floor_state
+            .explored
+            .iter()
+            .map(|tile| tile.to_array())
+            .collect::<Vec<_>>();
+        explored.sort_unstable();
+
+        let save = SaveData {
+            version: SAVE_VERSION,
+            saved_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_secs()),
+            seed: seed.0,
+            state: state.get().clone(),
+            tilemap: SavedTilemap::new(
+                current,
+                tile_world.tiles_in(floor::room_tile_origin(current), TILEMAP_SIZE),
+            ),
+            player: SavedPlayer {
+                translation: player_xform.translation.to_array(),
+                linvel: player_vel.linvel.to_array(),
+                health: player_health.current,
+                max_health: player_health.max,
+                inventory: player_inventory.slots.clone(),
+                equipped: player_inventory.equipped,
+                experience: *player_experience,
+            },
+            rooms,
+            edited_tilemaps,
+            opened_doors,
+            explored,
+            depth: floor_state.depth,
+        };
+        match slots.write(save_ev.slot, &save) {
+            Ok(()) => info!("saved to slot {}", save_ev.slot),
+            Err(err) => error!("failed to save to slot {}: {err}", save_ev.slot),
+        }
+    }
+}
+
+fn load_game(
+    mut load_evr: EventReader<LoadGame>,
+    slots: Res<SaveSlots>,
+    mut seed: ResMut<RunSeed>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut floor_state: ResMut<FloorState>,
+    mut tile_world: ResMut<TileWorld>,
+    mut player_qry: Query<
+        (
+            &mut Transform,
+            &mut Velocity,
+            &mut Health,
+            &mut Inventory,
+            &mut Experience,
+            &mut KinematicCharacterController,
+        ),
+        With<Player>,
+    >,
+) {
+    let Some(load_ev) = load_evr.read().last() else {
+        return;
+    };
+    let save = match slots.read(load_ev.slot) {
+        Ok(save) => save,
+        Err(err) => {
+            error!("failed to load slot {}: {err}", load_ev.slot);
+            return;
+        }
+    };
+
+    seed.0 = save.seed;
+    next_state.set(save.state);
+
+    floor_state.reset();
+    floor_state.depth = save.depth;
+    floor_state.current = IVec2::from_array(save.tilemap.room);
+    floor_state.rooms = save
+        .rooms
+        .iter()
+        .map(|saved_room| (IVec2::from_array(saved_room.room), saved_room.state))
+        .collect();
+    floor_state.snapshots = save
+        .edited_tilemaps
+        .iter()
+        .chain([&save.tilemap])
+        .map(SavedTilemap::tiles)
+        .collect();
+    floor_state.opened_doors = save.opened_doors.into_iter().collect();
+    floor_state.explored = save
+        .explored
+        .iter()
+        .copied()
+        .map(IVec2::from_array)
+        .collect();
+    tile_world.clear();
+
+    if let Ok((
+        mut player_xform,
+        mut player_vel,
+        mut player_health,
+        mut player_inventory,
+        mut player_experience,
+        mut player_kcc,
+    )) = player_qry.get_single_mut()
+    {
+        player_xform.translation = Vec3::from_array(save.player.translation);
+        player_vel.linvel = Vec2::from_array(save.player.linvel);
+        player_health.current = save.player.health;
+        player_health.max = save.player.max_health;
+        player_inventory.slots = save.player.inventory;
+        player_inventory.equipped = save.player.equipped;
+        *player_experience = save.player.experience;
+        player_kcc.translation = None;
+    }
+    info!("loaded slot {}", load_ev.slot);
+}
+
+fn parse_slot(args: &[&str]) -> Result<u8, String> {
+    match console::parse_args::<u8>(args)?[..] {
+        [slot] if slot < SLOT_COUNT => Ok(slot),
+        [slot] => Err(format!("slot {slot} out of range 0..{SLOT_COUNT}")),
+        _ => Err(String::from("expected a single slot number")),
+    }
+}
+
+fn save_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let slot = parse_slot(args)?;
+    world.send_event(SaveGame { slot });
+    Ok(String::new())
+}
+
+fn load_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let slot = parse_slot(args)?;
+    world.send_event(LoadGame { slot });
+    Ok(String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{player::PlayerAction, simulation::Simulation},
+    };
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let text = "(version: 999, saved_at: 0)";
+        assert!(matches!(
+            SaveData::from_ron(text),
+            Err(SaveError::UnsupportedVersion(999))
+        ));
+    }
+
+    #[test]
+    fn migrates_version_one_saves() {
+        let text = r#"(
+            version: 1,
+            saved_at: 42,
+            seed: 7,
+            state: Playing,
+            tilemap: (size: (32, 32), tiles: [(x: 0, y: 0, texture: 0)]),
+            player: (translation: (1.0, 2.0, 2.0), linvel: (0.0, 0.0), health: 3, max_health: 5),
+        )"#;
+        let save = SaveData::from_ron(text).unwrap();
+
+        assert_eq!(save.version, SAVE_VERSION);
+        assert_eq!(save.seed, 7);
+        assert_eq!(save.player.health, 3);
+        assert_eq!(save.player.inventory.len(), PLAYER_INVENTORY_SLOTS);
+        assert_eq!(save.player.inventory[0], Some(ItemStack::new("sword", 1)));
+        assert!(save.player.inventory[1..].iter().all(Option::is_none));
+        assert_eq!(save.player.equipped, Some(0));
+        assert_eq!(save.tilemap.room, [0, 0]);
+        assert!(save.rooms.is_empty());
+    }
+
+    #[test]
+    fn loading_restores_saved_run() {
+        let dir = std::env::temp_dir().join(format!("duhnjyn-save-test-{}", std::process::id()));
+        let mut sim = Simulation::with(|app| {
+            app.insert_resource(SaveSlots { dir: dir.clone() });
+        });
+        sim.settle(256);
+        let saved_translation = sim.player::<Transform>().translation;
+        let neighbour = sim
+            .app
+            .world
+            .resource::<FloorLayout>()
+            .neighbours(IVec2::ZERO)
+            .next()
+            .unwrap();
+        let neighbour_tile = sim
+            .app
+            .world
+            .resource::<TileWorld>()
+            .tiles_in(floor::room_tile_origin(neighbour), TILEMAP_SIZE)
+            .next()
+            .unwrap()
+            .0;
+        sim.app
+            .world
+            .resource_mut::<TileWorld>()
+            .remove(neighbour_tile);
+
+        sim.app.world.send_event(SaveGame { slot: 1 });
+        sim.step(&[]);
+        let saved = sim.app.world.resource::<SaveSlots>().read(1).unwrap();
+        assert_eq!(SaveData::from_ron(&saved.to_ron().unwrap()).unwrap(), saved);
+        assert!(saved
+            .edited_tilemaps
+            .iter()
+            .any(|tilemap| tilemap.room == neighbour.to_array()));
+
+        sim.step_n(30, &[PlayerAction::MoveRight]);
+        assert_ne!(sim.player::<Transform>().translation, saved_translation);
+
+        sim.app.world.send_event(LoadGame { slot: 1 });
+        sim.step(&[]);
+        assert_eq!(
+            sim.player::<Transform>().translation,
+            Vec3::from_array(saved.player.translation)
+        );
+        sim.step(&[]);
+        assert_eq!(
+            sim.app.world.resource::<FloorState>().explored,
+            saved
+                .explored
+                .iter()
+                .copied()
+                .map(IVec2::from_array)
+                .collect()
+        );
+        let tile_world = sim.app.world.resource::<TileWorld>();
+        assert!(tile_world.get(neighbour_tile).is_none());
+        assert!(tile_world
+            .tiles_in(floor::room_tile_origin(neighbour), TILEMAP_SIZE)
+            .next()
+            .is_some());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
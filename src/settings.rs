@@ -0,0 +1,578 @@
+use {
+    super::{
+        game_state::GameState,
+        player::{Player, PlayerAction},
+    },
+    bevy::{
+        app::AppExit,
+        prelude::*,
+        window::{PresentMode, PrimaryWindow, WindowMode, WindowResolution},
+    },
+    leafwing_input_manager::prelude::*,
+    serde::{Deserialize, Serialize},
+    std::{env, fmt, fs, io, path::PathBuf},
+};
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Settings>()
+            .add_systems(Update, (apply_video_settings, apply_input_settings))
+            .add_systems(OnExit(GameState::Settings), save_settings)
+            .add_systems(Last, save_settings.run_if(on_event::<AppExit>()));
+    }
+}
+
+pub const RESOLUTIONS: [[u32; 2]; 4] = [[1280, 720], [1600, 900], [1920, 1080], [2560, 1440]];
+const MIN_RESOLUTION: [u32; 2] = [640, 360];
+const MAX_RESOLUTION: [u32; 2] = [7680, 4320];
+const VOLUME_STEP: f32 = 0.1;
+const UI_SCALE_STEP: f32 = 0.25;
+const UI_SCALE_RANGE: (f32, f32) = (0.5, 2.);
+const CONFIG_DIR: &str = "duhnjyn";
+const SETTINGS_FILE: &str = "settings.ron";
+const BINDABLE_KEYS: [KeyCode; 46] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Space,
+    KeyCode::Tab,
+    KeyCode::Return,
+    KeyCode::ShiftLeft,
+    KeyCode::ControlLeft,
+    KeyCode::AltLeft,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+];
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Parse(err) => write!(f, "{err}"),
+            Self::Serialize(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl From<io::Error> for SettingsError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for SettingsError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl From<ron::Error> for SettingsError {
+    fn from(err: ron::Error) -> Self {
+        Self::Serialize(err)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DisplayMode {
+    #[default]
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl DisplayMode {
+    const ALL: [Self; 3] = [Self::Windowed, Self::Borderless, Self::Fullscreen];
+
+    pub fn cycle(self, step: i32) -> Self {
+        let idx = Self::ALL
+            .iter()
+            .position(|&mode| mode == self)
+            .unwrap_or_default();
+        Self::ALL[(idx as i32 + step).rem_euclid(Self::ALL.len() as i32) as usize]
+    }
+
+    pub fn window_mode(self) -> WindowMode {
+        match self {
+            Self::Windowed => WindowMode::Windowed,
+            Self::Borderless => WindowMode::BorderlessFullscreen,
+            Self::Fullscreen => WindowMode::Fullscreen,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VolumeBus {
+    Master,
+    Music,
+    Sfx,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct VideoSettings {
+    pub resolution: [u32; 2],
+    pub display_mode: DisplayMode,
+    pub vsync: bool,
+}
+
+impl Default for VideoSettings {
+    fn default() -> Self {
+        Self {
+            resolution: RESOLUTIONS[0],
+            display_mode: DisplayMode::Windowed,
+            vsync: false,
+        }
+    }
+}
+
+impl VideoSettings {
+    pub fn cycle_resolution(&mut self, step: i32) {
+        let idx = RESOLUTIONS
+            .iter()
+            .position(|&resolution| resolution == self.resolution)
+            .unwrap_or_default();
+        self.resolution =
+            RESOLUTIONS[(idx as i32 + step).rem_euclid(RESOLUTIONS.len() as i32) as usize];
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+
+    pub fn window(&self) -> Window {
+        let [width, height] = self.resolution;
+        Window {
+            present_mode: self.present_mode(),
+            mode: self.display_mode.window_mode(),
+            resolution: WindowResolution::new(width as f32, height as f32),
+            position: WindowPosition::Centered(MonitorSelection::Primary),
+            title: String::from("Duhnjyn"),
+            ..default()
+        }
+    }
+
+    fn validate(&mut self) {
+        for axis in 0..2 {
+            self.resolution[axis] =
+                self.resolution[axis].clamp(MIN_RESOLUTION[axis], MAX_RESOLUTION[axis]);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master: 1.,
+            music: 0.8,
+            sfx: 1.,
+        }
+    }
+}
+
+impl AudioSettings {
+    pub fn volume(&self, bus: VolumeBus) -> f32 {
+        match bus {
+            VolumeBus::Master => self.master,
+            VolumeBus::Music => self.music,
+            VolumeBus::Sfx => self.sfx,
+        }
+    }
+
+    pub fn adjust(&mut self, bus: VolumeBus, step: i32) {
+        let volume = match bus {
+            VolumeBus::Master => &mut self.master,
+            VolumeBus::Music => &mut self.music,
+            VolumeBus::Sfx => &mut self.sfx,
+        };
+        *volume = ((*volume + step as f32 * VOLUME_STEP) * 10.)
+            .round()
+            .clamp(0., 10.)
+            / 10.;
+    }
+
+    fn validate(&mut self) {
+        let defaults = Self::default();
+        for (volume, default) in [
+            (&mut self.master, defaults.master),
+            (&mut self.music, defaults.music),
+            (&mut self.sfx, defaults.sfx),
+        ] {
+            *volume = if volume.is_finite() {
+                volume.clamp(0., 1.)
+            } else {
+                default
+            };
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct InputSettings {
+    pub keys: Vec<(PlayerAction, String)>,
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        Self {
+            keys: [
+                (PlayerAction::MoveLeft, KeyCode::A),
+                (PlayerAction::MoveRight, KeyCode::D),
+                (PlayerAction::MoveUp, KeyCode::W),
+                (PlayerAction::MoveDown, KeyCode::S),
+                (PlayerAction::Jump, KeyCode::Space),
+                (PlayerAction::CycleEquipment, KeyCode::Q),
+                (PlayerAction::Interact, KeyCode::E),
+                (PlayerAction::ToggleMap, KeyCode::M),
+            ]
+            .into_iter()
+            .map(|(action, key_code)| (action, format!("{key_code:?}")))
+            .collect(),
+        }
+    }
+}
+
+impl InputSettings {
+    pub fn key_code(name: &str) -> Option<KeyCode> {
+        BINDABLE_KEYS
+            .into_iter()
+            .find(|key_code| format!("{key_code:?}") == name)
+    }
+
+    pub fn bindable(key_code: KeyCode) -> bool {
+        BINDABLE_KEYS.contains(&key_code)
+    }
+
+    pub fn key(&self, action: PlayerAction) -> Option<&str> {
+        self.keys
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .map(|(_, name)| name.as_str())
+    }
+
+    pub fn rebind(&mut self, action: PlayerAction, key_code: KeyCode) {
+        let name = format!("{key_code:?}");
+        let Some(old) = self.key(action).map(String::from) else {
+            return;
+        };
+        for (bound, bound_name) in self.keys.iter_mut() {
+            if *bound == action {
+                *bound_name = name.clone();
+            } else if *bound_name == name {
+                *bound_name = old.clone();
+            }
+        }
+    }
+
+    pub fn input_map(&self) -> InputMap<PlayerAction> {
+        let mut input_map = InputMap::default();
+        for (action, name) in self.keys.iter() {
+            if let Some(key_code) = Self::key_code(name) {
+                input_map.insert(key_code, *action);
+            }
+        }
+        input_map.insert(MouseButton::Left, PlayerAction::Attack);
+        input_map
+    }
+
+    fn validate(&mut self) {
+        let mut actions = Vec::new();
+        let mut key_codes = Vec::new();
+        for (action, name) in self.keys.iter() {
+            let Some(key_code) = Self::key_code(name) else {
+                *self = Self::default();
+                return;
+            };
+            if actions.contains(action) || key_codes.contains(&key_code) {
+                *self = Self::default();
+                return;
+            }
+            actions.push(*action);
+            key_codes.push(key_code);
+        }
+        if Self::default()
+            .keys
+            .iter()
+            .any(|(action, _)| !actions.contains(action))
+        {
+            *self = Self::default();
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    pub ui_scale: f32,
+    pub reduce_motion: bool,
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            ui_scale: 1.,
+            reduce_motion: false,
+        }
+    }
+}
+
+impl AccessibilitySettings {
+    pub fn adjust_ui_scale(&mut self, step: i32) {
+        self.ui_scale =
+            (self.ui_scale + step as f32 * UI_SCALE_STEP).clamp(UI_SCALE_RANGE.0, UI_SCALE_RANGE.1);
+    }
+
+    fn validate(&mut self) {
+        self.ui_scale = if self.ui_scale.is_finite() {
+            self.ui_scale.clamp(UI_SCALE_RANGE.0, UI_SCALE_RANGE.1)
+        } else {
+            1.
+        };
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub video: VideoSettings,
+    pub audio: AudioSettings,
+    pub input: InputSettings,
+    pub accessibility: AccessibilitySettings,
+}
+
+impl Settings {
+    pub fn from_ron(text: &str) -> Result<Self, SettingsError> {
+        let mut settings = ron::from_str::<Self>(text)?;
+        settings.video.validate();
+        settings.audio.validate();
+        settings.input.validate();
+        settings.accessibility.validate();
+        Ok(settings)
+    }
+
+    pub fn to_ron(&self) -> Result<String, SettingsError> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+}
+
+#[derive(Resource)]
+pub struct SettingsFile {
+    pub path: PathBuf,
+}
+
+impl Default for SettingsFile {
+    fn default() -> Self {
+        let dir = config_dir().map_or_else(PathBuf::new, |dir| dir.join(CONFIG_DIR));
+        Self {
+            path: dir.join(SETTINGS_FILE),
+        }
+    }
+}
+
+fn config_dir() -> Option<PathBuf> {
+    let var = |name: &str| {
+        env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+    if cfg!(windows) {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        var("XDG_CONFIG_HOME").or_else(|| var("HOME").map(|home| home.join(".config")))
+    }
+}
+
+impl SettingsFile {
+    pub fn read(&self) -> Result<Settings, SettingsError> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => Settings::from_ron(&text),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Settings::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn write(&self, settings: &Settings) -> Result<(), SettingsError> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, settings.to_ron()?)?;
+        Ok(())
+    }
+}
+
+fn apply_video_settings(
+    settings: Res<Settings>,
+    ui_scale: Option<ResMut<UiScale>>,
+    mut primary_win_qry: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let [width, height] = settings.video.resolution;
+    for mut primary_win in primary_win_qry.iter_mut() {
+        primary_win.resolution.set(width as f32, height as f32);
+        primary_win.mode = settings.video.display_mode.window_mode();
+        primary_win.present_mode = settings.video.present_mode();
+    }
+    if let Some(mut ui_scale) = ui_scale {
+        ui_scale.0 = settings.accessibility.ui_scale as f64;
+    }
+}
+
+fn apply_input_settings(
+    settings: Res<Settings>,
+    mut player_qry: Query<&mut InputMap<PlayerAction>, With<Player>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for mut player_input_map in player_qry.iter_mut() {
+        *player_input_map = settings.input.input_map();
+    }
+}
+
+fn save_settings(settings: Res<Settings>, settings_file: Option<Res<SettingsFile>>) {
+    let Some(settings_file) = settings_file else {
+        return;
+    };
+    if let Err(err) = settings_file.write(&settings) {
+        error!(
+            "failed to save settings to {}: {err}",
+            settings_file.path.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_round_trip_through_the_settings_file() {
+        let path = std::env::temp_dir().join(format!(
+            "duhnjyn-settings-test-{}/settings.ron",
+            std::process::id()
+        ));
+        let settings_file = SettingsFile { path: path.clone() };
+        assert_eq!(settings_file.read().unwrap(), Settings::default());
+
+        let mut settings = Settings::default();
+        settings.video.cycle_resolution(2);
+        settings.audio.adjust(VolumeBus::Music, -3);
+        settings.accessibility.reduce_motion = true;
+        settings_file.write(&settings).unwrap();
+        assert_eq!(settings_file.read().unwrap(), settings);
+
+        fs::write(&path, "(video: (resolution: (1280,").unwrap();
+        assert!(matches!(settings_file.read(), Err(SettingsError::Parse(_))));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn invalid_values_fall_back_to_defaults() {
+        let settings = Settings::from_ron(
+            r#"(
+                video: (resolution: (10, 100000)),
+                audio: (master: 3.5, sfx: -1.0),
+                input: (keys: [(Jump, "Banana")]),
+                accessibility: (ui_scale: 9.0),
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(settings.video.resolution, [640, 4320]);
+        assert_eq!(settings.video.display_mode, DisplayMode::Windowed);
+        assert_eq!((settings.audio.master, settings.audio.sfx), (1., 0.));
+        assert_eq!(settings.audio.music, AudioSettings::default().music);
+        assert_eq!(settings.input, InputSettings::default());
+        assert_eq!(settings.accessibility.ui_scale, 2.);
+
+        let settings =
+            Settings::from_ron(r#"(input: (keys: [(Jump, "W"), (MoveLeft, "D")]))"#).unwrap();
+        assert_eq!(settings.input, InputSettings::default());
+
+        let mut input = InputSettings::default();
+        input.rebind(PlayerAction::MoveLeft, KeyCode::D);
+        input.rebind(PlayerAction::Jump, KeyCode::W);
+        let settings = Settings::from_ron(
+            &Settings {
+                input: input.clone(),
+                ..default()
+            }
+            .to_ron()
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(settings.input, input);
+        assert_eq!(input.key(PlayerAction::MoveRight), Some("A"));
+    }
+
+    #[test]
+    fn settings_file_lives_in_the_config_dir() {
+        let path = SettingsFile::default().path;
+        match config_dir() {
+            Some(dir) => assert_eq!(path, dir.join(CONFIG_DIR).join(SETTINGS_FILE)),
+            None => assert_eq!(path, PathBuf::from(SETTINGS_FILE)),
+        }
+    }
+}
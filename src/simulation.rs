@@ -0,0 +1,170 @@
+use {
+    super::{
+        aim::AimPlugin,
+        animation::AnimationPlugin,
+        autotile::AutotilePlugin,
+        console::ConsolePlugin,
+        door::DoorPlugin,
+        enemy::EnemyPlugin,
+        floor::FloorPlugin,
+        game_state::GameState,
+        health::HealthPlugin,
+        hud::HudPlugin,
+        inventory::InventoryPlugin,
+        lighting::LightingPlugin,
+        loot::LootPlugin,
+        menu::MenuPlugin,
+        minimap::MinimapPlugin,
+        physics::{Grounded, PhysicsPlugin, TIMESTEP},
+        player::{Player, PlayerAction, PlayerPlugin},
+        projectile::ProjectilePlugin,
+        replay::ReplayPlugin,
+        rng::{PinnedSeed, RunSeed},
+        save::SavePlugin,
+        settings::SettingsPlugin,
+        sound::SoundPlugin,
+        sprite_flip::SpriteFlipPlugin,
+        stats::StatsPlugin,
+        status_effect::StatusEffectPlugin,
+        tile::TilePlugin,
+        time_control::TimeControlPlugin,
+        trap::TrapPlugin,
+        weapon::WeaponPlugin,
+    },
+    bevy::{
+        input::InputPlugin, prelude::*, time::TimeUpdateStrategy, utils::Instant,
+        window::ExitCondition,
+    },
+    bevy_rapier2d::prelude::*,
+    leafwing_input_manager::prelude::*,
+};
+
+pub const SEED: u64 = 0;
+
+pub struct Simulation {
+    pub app: App,
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        Self::with(|_| {})
+    }
+
+    pub fn with(setup: impl FnOnce(&mut App)) -> Self {
+        let mut app = App::new();
+        app.add_state::<GameState>()
+            .add_plugins((
+                MinimalPlugins,
+                AssetPlugin::default(),
+                TransformPlugin,
+                HierarchyPlugin,
+                InputPlugin,
+                WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    close_when_requested: false,
+                },
+                RapierPhysicsPlugin::<NoUserData>::default(),
+            ))
+            .add_plugins((
+                ReplayPlugin,
+                PlayerPlugin,
+                TilePlugin,
+                FloorPlugin,
+                AutotilePlugin,
+                PhysicsPlugin,
+                SpriteFlipPlugin,
+                AnimationPlugin,
+                AimPlugin,
+                SavePlugin,
+                SettingsPlugin,
+                ConsolePlugin,
+                SoundPlugin,
+            ))
+            .add_plugins((
+                InventoryPlugin,
+                DoorPlugin,
+                EnemyPlugin,
+                WeaponPlugin,
+                HealthPlugin,
+                ProjectilePlugin,
+                LootPlugin,
+                StatusEffectPlugin,
+                StatsPlugin,
+                TimeControlPlugin,
+                TrapPlugin,
+                LightingPlugin,
+                MinimapPlugin,
+                HudPlugin,
+                MenuPlugin,
+            ))
+            .init_asset::<Image>()
+            .init_asset::<TextureAtlas>()
+            .init_asset::<AudioSource>()
+            .insert_resource(Time::<Fixed>::from_duration(TIMESTEP))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(TIMESTEP))
+            .insert_resource(RapierConfiguration {
+                timestep_mode: TimestepMode::Fixed {
+                    dt: TIMESTEP.as_secs_f32(),
+                    substeps: 1,
+                },
+                ..default()
+            })
+            .insert_resource(NextState(Some(GameState::Playing)))
+            .insert_resource(RunSeed(SEED))
+            .insert_resource(PinnedSeed);
+        setup(&mut app);
+        app.update();
+
+        Self { app }
+    }
+
+    pub fn step(&mut self, actions: &[PlayerAction]) {
+        let mut player_actions = self
+            .app
+            .world
+            .query_filtered::<&mut ActionState<PlayerAction>, With<Player>>()
+            .single_mut(&mut self.app.world);
+
+        let now = Instant::now();
+        player_actions.tick(now, now);
+        for action in PlayerAction::variants() {
+            if actions.contains(&action) {
+                player_actions.press(action);
+            } else {
+                player_actions.release(action);
+            }
+        }
+        self.app.update();
+    }
+
+    pub fn step_n(&mut self, ticks: usize, actions: &[PlayerAction]) {
+        for _ in 0..ticks {
+            self.step(actions);
+        }
+    }
+
+    pub fn settle(&mut self, max_ticks: usize) {
+        for _ in 0..max_ticks {
+            if self.player::<Grounded>().0 {
+                return;
+            }
+            self.step(&[]);
+        }
+        panic!("player did not settle within {max_ticks} ticks");
+    }
+
+    pub fn player<C: Component>(&mut self) -> &C {
+        self.app
+            .world
+            .query_filtered::<&C, With<Player>>()
+            .single(&self.app.world)
+    }
+
+    pub fn player_mut<C: Component>(&mut self) -> Mut<C> {
+        self.app
+            .world
+            .query_filtered::<&mut C, With<Player>>()
+            .single_mut(&mut self.app.world)
+    }
+}
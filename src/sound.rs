@@ -0,0 +1,389 @@
+use {
+    super::{
+        floor::ROOM_EXTENT,
+        game_state::GameState,
+        health::{DamageEvent, Health},
+        physics::Grounded,
+        player::{Player, PlayerCamera, FOOTSTEP_FRAMES},
+        settings::{Settings, VolumeBus},
+        weapon::Weapon,
+    },
+    bevy::{
+        audio::Volume,
+        prelude::*,
+        utils::{HashMap, HashSet},
+    },
+    bevy_rapier2d::prelude::*,
+};
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlaySound>().add_systems(
+            Update,
+            (
+                (footstep_sounds, movement_sounds, swing_sounds, hit_sounds)
+                    .run_if(in_state(GameState::Playing)),
+                attach_listener,
+                play_sounds,
+                (switch_music, crossfade_music).chain(),
+            )
+                .chain(),
+        );
+    }
+}
+
+pub const SPATIAL_SCALE: f32 = 1. / FULL_VOLUME_DISTANCE;
+const HEARING_RANGE: f32 = ROOM_EXTENT;
+const FULL_VOLUME_DISTANCE: f32 = 48.;
+const EAR_GAP: f32 = 8.;
+const CROSSFADE_SECONDS: f32 = 1.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sound {
+    Footstep,
+    Jump,
+    Land,
+    Swing,
+    Hit,
+}
+
+impl Sound {
+    fn path(self) -> &'static str {
+        match self {
+            Self::Footstep => "audio/footstep.wav",
+            Self::Jump => "audio/jump.wav",
+            Self::Land => "audio/land.wav",
+            Self::Swing => "audio/swing.wav",
+            Self::Hit => "audio/hit.wav",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MusicTrack {
+    Menu,
+    Dungeon,
+}
+
+impl MusicTrack {
+    fn for_state(state: &GameState) -> Self {
+        match state {
+            GameState::MainMenu | GameState::Settings => Self::Menu,
+            GameState::Playing | GameState::Paused => Self::Dungeon,
+        }
+    }
+
+    fn path(self) -> &'static str {
+        match self {
+            Self::Menu => "audio/menu.wav",
+            Self::Dungeon => "audio/dungeon.wav",
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct PlaySound {
+    pub sound: Sound,
+    pub pos: Vec2,
+}
+
+#[derive(Component)]
+pub struct SoundEffect;
+
+#[derive(Component)]
+pub struct Music {
+    pub track: MusicTrack,
+    pub level: f32,
+    pub fading_out: bool,
+}
+
+fn bus_volume(settings: &Settings, bus: VolumeBus) -> f32 {
+    settings.audio.volume(VolumeBus::Master) * settings.audio.volume(bus)
+}
+
+fn attach_listener(mut cmds: Commands, cam_qry: Query<Entity, Added<PlayerCamera>>) {
+    for cam_id in cam_qry.iter() {
+        cmds.entity(cam_id).insert(SpatialListener::new(EAR_GAP));
+    }
+}
+
+fn footstep_sounds(
+    mut last_frames: Local<HashMap<Entity, usize>>,
+    mut play_sound_evw: EventWriter<PlaySound>,
+    player_qry: Query<(Entity, &TextureAtlasSprite, &Grounded, &GlobalTransform), With<Player>>,
+) {
+    for (player_id, player_tex_atlas_sprite, player_grounded, player_glob_xform) in
+        player_qry.iter()
+    {
+        let frame = player_tex_atlas_sprite.index;
+        if last_frames.insert(player_id, frame) == Some(frame) {
+            continue;
+        }
+        if player_grounded.0 && FOOTSTEP_FRAMES.contains(&frame) {
+            play_sound_evw.send(PlaySound {
+                sound: Sound::Footstep,
+                pos: player_glob_xform.translation().truncate(),
+            });
+        }
+    }
+}
+
+fn movement_sounds(
+    mut was_grounded: Local<HashMap<Entity, bool>>,
+    mut play_sound_evw: EventWriter<PlaySound>,
+    actor_qry: Query<(Entity, &Grounded, &Velocity, &GlobalTransform), With<Health>>,
+) {
+    for (actor_id, actor_grounded, actor_vel, actor_glob_xform) in actor_qry.iter() {
+        let Some(prev_grounded) = was_grounded.insert(actor_id, actor_grounded.0) else {
+            continue;
+        };
+        let sound = match (prev_grounded, actor_grounded.0) {
+            (true, false) if actor_vel.linvel.y > 0. => Sound::Jump,
+            (false, true) => Sound::Land,
+            _ => continue,
+        };
+        play_sound_evw.send(PlaySound {
+            sound,
+            pos: actor_glob_xform.translation().truncate(),
+        });
+    }
+}
+
+fn swing_sounds(
+    mut swinging: Local<HashSet<Entity>>,
+    mut play_sound_evw: EventWriter<PlaySound>,
+    weapon_qry: Query<(Entity, &Weapon, &GlobalTransform)>,
+) {
+    for (weapon_id, weapon, weapon_glob_xform) in weapon_qry.iter() {
+        if weapon.cooldown() == 0. {
+            swinging.remove(&weapon_id);
+        } else if swinging.insert(weapon_id) {
+            play_sound_evw.send(PlaySound {
+                sound: Sound::Swing,
+                pos: weapon_glob_xform.translation().truncate(),
+            });
+        }
+    }
+}
+
+fn hit_sounds(
+    mut damage_evr: EventReader<DamageEvent>,
+    mut play_sound_evw: EventWriter<PlaySound>,
+    target_qry: Query<&GlobalTransform, With<Health>>,
+) {
+    for damage in damage_evr.read() {
+        if let Ok(target_glob_xform) = target_qry.get(damage.target) {
+            play_sound_evw.send(PlaySound {
+                sound: Sound::Hit,
+                pos: target_glob_xform.translation().truncate(),
+            });
+        }
+    }
+}
+
+fn play_sounds(
+    mut cmds: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    mut play_sound_evr: EventReader<PlaySound>,
+    cam_qry: Query<&GlobalTransform, With<PlayerCamera>>,
+) {
+    let listener_pos = cam_qry
+        .get_single()
+        .ok()
+        .map(|cam_glob_xform| cam_glob_xform.translation().truncate());
+
+    let volume = bus_volume(&settings, VolumeBus::Sfx);
+    for play_sound in play_sound_evr.read() {
+        let out_of_range = listener_pos
+            .is_some_and(|listener_pos| listener_pos.distance(play_sound.pos) >= HEARING_RANGE);
+        if volume <= 0. || out_of_range {
+            continue;
+        }
+        cmds.spawn((
+            SoundEffect,
+            Name::new(format!("{:?} sound", play_sound.sound)),
+            AudioBundle {
+                source: asset_server.load(play_sound.sound.path()),
+                settings: PlaybackSettings::DESPAWN
+                    .with_volume(Volume::new_relative(volume))
+                    .with_spatial(listener_pos.is_some()),
+            },
+            TransformBundle::from_transform(Transform::from_translation(play_sound.pos.extend(0.))),
+        ));
+    }
+}
+
+fn switch_music(
+    mut cmds: Commands,
+    asset_server: Res<AssetServer>,
+    state: Res<State<GameState>>,
+    mut music_qry: Query<&mut Music>,
+) {
+    if !state.is_changed() {
+        return;
+    }
+    let track = MusicTrack::for_state(state.get());
+    let mut playing = false;
+    for mut music in music_qry.iter_mut() {
+        music.fading_out = music.track != track;
+        playing |= !music.fading_out;
+    }
+    if playing {
+        return;
+    }
+    cmds.spawn((
+        Music {
+            track,
+            level: 0.,
+            fading_out: false,
+        },
+        Name::new(format!("{track:?} music")),
+        AudioBundle {
+            source: asset_server.load(track.path()),
+            settings: PlaybackSettings::LOOP.with_volume(Volume::new_relative(0.)),
+        },
+    ));
+}
+
+fn crossfade_music(
+    mut cmds: Commands,
+    time: Res<Time<Real>>,
+    settings: Res<Settings>,
+    mut music_qry: Query<(Entity, &mut Music, Option<&AudioSink>)>,
+) {
+    let step = time.delta_seconds() / CROSSFADE_SECONDS;
+
+    for (music_id, mut music, sink) in music_qry.iter_mut() {
+        let level = if music.fading_out {
+            music.level - step
+        } else {
+            music.level + step
+        };
+        music.level = level.clamp(0., 1.);
+        if music.fading_out && music.level == 0. {
+            cmds.entity(music_id).despawn();
+            continue;
+        }
+        if let Some(sink) = sink {
+            sink.set_volume(music.level * bus_volume(&settings, VolumeBus::Music));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{player::PlayerAction, simulation::Simulation},
+    };
+
+    fn sound_names(sim: &mut Simulation) -> Vec<String> {
+        sim.app
+            .world
+            .query_filtered::<&Name, With<SoundEffect>>()
+            .iter(&sim.app.world)
+            .map(|name| name.as_str().to_owned())
+            .collect()
+    }
+
+    fn music(sim: &mut Simulation) -> Vec<(MusicTrack, f32, bool)> {
+        sim.app
+            .world
+            .query::<&Music>()
+            .iter(&sim.app.world)
+            .map(|music| (music.track, music.level, music.fading_out))
+            .collect()
+    }
+
+    #[test]
+    fn sounds_out_of_hearing_range_are_skipped() {
+        let mut sim = Simulation::new();
+        sim.step_n(2, &[]);
+        let cam_pos = sim
+            .app
+            .world
+            .query_filtered::<&GlobalTransform, With<PlayerCamera>>()
+            .single(&sim.app.world)
+            .translation()
+            .truncate();
+        for offset in [0., HEARING_RANGE * 2.] {
+            sim.app.world.send_event(PlaySound {
+                sound: Sound::Swing,
+                pos: cam_pos + Vec2::X * offset,
+            });
+        }
+        sim.step(&[]);
+
+        let swings = sim
+            .app
+            .world
+            .query_filtered::<(&Name, &Transform), With<SoundEffect>>()
+            .iter(&sim.app.world)
+            .filter(|(name, _)| name.as_str() == "Swing sound")
+            .map(|(_, xform)| xform.translation.truncate())
+            .collect::<Vec<_>>();
+        assert_eq!(swings, vec![cam_pos]);
+    }
+
+    #[test]
+    fn player_actions_trigger_sounds() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        sim.step_n(2, &[]);
+        assert!(sound_names(&mut sim).contains(&String::from("Land sound")));
+
+        sim.step(&[PlayerAction::Jump]);
+        sim.step_n(2, &[]);
+        assert!(sound_names(&mut sim).contains(&String::from("Jump sound")));
+
+        sim.settle(256);
+        sim.step_n(128, &[PlayerAction::MoveRight]);
+        assert!(sound_names(&mut sim).contains(&String::from("Footstep sound")));
+
+        let player_id = sim
+            .app
+            .world
+            .query_filtered::<Entity, With<Player>>()
+            .single(&sim.app.world);
+        sim.app.world.send_event(DamageEvent {
+            target: player_id,
+            amount: 0,
+            knockback: Vec2::ZERO,
+        });
+        sim.step(&[]);
+        assert!(sound_names(&mut sim).contains(&String::from("Hit sound")));
+    }
+
+    #[test]
+    fn music_crossfades_between_states() {
+        let mut sim = Simulation::new();
+        sim.step_n(128, &[]);
+        assert_eq!(music(&mut sim), vec![(MusicTrack::Dungeon, 1., false)]);
+
+        sim.app
+            .world
+            .insert_resource(NextState(Some(GameState::MainMenu)));
+        sim.app.update();
+        sim.app.update();
+        let tracks = music(&mut sim);
+        assert_eq!(tracks.len(), 2);
+        assert!(tracks
+            .iter()
+            .any(|&(track, level, fading_out)| track == MusicTrack::Menu
+                && level > 0.
+                && !fading_out));
+        assert!(tracks
+            .iter()
+            .any(|&(track, level, fading_out)| track == MusicTrack::Dungeon
+                && level < 1.
+                && fading_out));
+
+        for _ in 0..128 {
+            sim.app.update();
+        }
+        assert_eq!(music(&mut sim), vec![(MusicTrack::Menu, 1., false)]);
+    }
+}
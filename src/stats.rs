@@ -0,0 +1,279 @@
+use {
+    super::{
+        console::{self, AddConsoleCommand},
+        game_state::GameState,
+        health::Health,
+        physics::{self, Acceleration, JumpVelocity, TerminalVelocity},
+        player::{self, Player},
+    },
+    bevy::{prelude::*, utils::HashMap},
+    serde::{Deserialize, Serialize},
+};
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GainXp>()
+            .add_systems(
+                FixedUpdate,
+                (gain_xp, apply_level_modifiers, apply_stats)
+                    .chain()
+                    .after(player::player_movement)
+                    .before(physics::apply_forces)
+                    .distributive_run_if(in_state(GameState::Playing)),
+            )
+            .add_console_command("xp", "xp <amount>: grant the player experience", grant_xp);
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stat {
+    MoveSpeed,
+    Acceleration,
+    JumpVelocity,
+    FallSpeed,
+    Gravity,
+    MaxHealth,
+    Damage,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModifierKind {
+    Flat,
+    Percent,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct StatModifier {
+    pub stat: Stat,
+    pub kind: ModifierKind,
+    pub value: f32,
+}
+
+impl StatModifier {
+    pub fn flat(stat: Stat, value: f32) -> Self {
+        Self {
+            stat,
+            kind: ModifierKind::Flat,
+            value,
+        }
+    }
+
+    pub fn percent(stat: Stat, value: f32) -> Self {
+        Self {
+            stat,
+            kind: ModifierKind::Percent,
+            value,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ModifierSource {
+    Equipment,
+    Status,
+    Level,
+}
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct Stats {
+    base: HashMap<Stat, f32>,
+    modifiers: HashMap<ModifierSource, Vec<StatModifier>>,
+}
+
+impl Stats {
+    pub fn new(base: impl IntoIterator<Item = (Stat, f32)>) -> Self {
+        Self {
+            base: base.into_iter().collect(),
+            modifiers: HashMap::default(),
+        }
+    }
+
+    pub fn has(&self, stat: Stat) -> bool {
+        self.base.contains_key(&stat)
+    }
+
+    pub fn get(&self, stat: Stat) -> f32 {
+        self.modify(stat, self.base.get(&stat).copied().unwrap_or_default())
+    }
+
+    pub fn modify(&self, stat: Stat, base: f32) -> f32 {
+        let (flat, percent) = self
+            .modifiers
+            .values()
+            .flatten()
+            .filter(|modifier| modifier.stat == stat)
+            .fold((0., 0.), |(flat, percent), modifier| match modifier.kind {
+                ModifierKind::Flat => (flat + modifier.value, percent),
+                ModifierKind::Percent => (flat, percent + modifier.value),
+            });
+        ((base + flat) * (1. + percent)).max(0.)
+    }
+
+    pub fn damage(&self, base: u32) -> u32 {
+        self.modify(Stat::Damage, base as f32).round() as u32
+    }
+
+    pub fn modifiers(&self, source: ModifierSource) -> &[StatModifier] {
+        self.modifiers
+            .get(&source)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn set_modifiers(&mut self, source: ModifierSource, modifiers: Vec<StatModifier>) {
+        self.modifiers.insert(source, modifiers);
+    }
+}
+
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Experience {
+    pub level: u32,
+    pub xp: u32,
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Self { level: 1, xp: 0 }
+    }
+}
+
+impl Experience {
+    pub fn xp_to_next_level(&self) -> u32 {
+        20 * self.level
+    }
+
+    pub fn gain(&mut self, amount: u32) -> u32 {
+        let start_level = self.level;
+        self.xp += amount;
+        while self.xp >= self.xp_to_next_level() {
+            self.xp -= self.xp_to_next_level();
+            self.level += 1;
+        }
+        self.level - start_level
+    }
+
+    pub fn modifiers(&self) -> Vec<StatModifier> {
+        let ranks = self.level.saturating_sub(1) as f32;
+        if ranks == 0. {
+            return Vec::new();
+        }
+        vec![
+            StatModifier::flat(Stat::MaxHealth, ranks),
+            StatModifier::percent(Stat::Damage, 0.1 * ranks),
+        ]
+    }
+}
+
+#[derive(Event)]
+pub struct GainXp {
+    pub target: Entity,
+    pub amount: u32,
+}
+
+fn gain_xp(mut gain_xp_evr: EventReader<GainXp>, mut experience_qry: Query<&mut Experience>) {
+    for gain_xp in gain_xp_evr.read() {
+        let Ok(mut experience) = experience_qry.get_mut(gain_xp.target) else {
+            continue;
+        };
+        if experience.gain(gain_xp.amount) > 0 {
+            info!("reached level {}", experience.level);
+        }
+    }
+}
+
+fn apply_level_modifiers(mut stats_qry: Query<(&Experience, &mut Stats), Changed<Experience>>) {
+    for (experience, mut stats) in stats_qry.iter_mut() {
+        let modifiers = experience.modifiers();
+        if stats.modifiers(ModifierSource::Level) != modifiers.as_slice() {
+            stats.set_modifiers(ModifierSource::Level, modifiers);
+        }
+    }
+}
+
+pub fn apply_stats(
+    mut stats_qry: Query<
+        (
+            &Stats,
+            Option<&mut TerminalVelocity>,
+            Option<&mut Acceleration>,
+            Option<&mut JumpVelocity>,
+            Option<&mut Health>,
+        ),
+        Changed<Stats>,
+    >,
+) {
+    for (stats, terminal_vel, acc, jump_vel, health) in stats_qry.iter_mut() {
+        if let Some(mut terminal_vel) = terminal_vel {
+            terminal_vel.0 = Vec2::new(stats.get(Stat::MoveSpeed), stats.get(Stat::FallSpeed));
+        }
+        if let Some(mut acc) = acc {
+            acc.0 = Vec2::new(stats.get(Stat::Acceleration), stats.get(Stat::Gravity));
+        }
+        if let Some(mut jump_vel) = jump_vel {
+            jump_vel.0 = stats.get(Stat::JumpVelocity);
+        }
+        if let Some(mut health) = health.filter(|_| stats.has(Stat::MaxHealth)) {
+            let max = stats.get(Stat::MaxHealth).round() as u32;
+            if max > health.max {
+                health.current += max - health.max;
+            }
+            health.max = max;
+            health.current = health.current.min(max);
+        }
+    }
+}
+
+fn grant_xp(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let &[amount] = &console::parse_args::<u32>(args)?[..] else {
+        return Err(String::from("usage: xp <amount>"));
+    };
+    let target = world
+        .query_filtered::<Entity, With<Player>>()
+        .get_single(world)
+        .map_err(|_| String::from("no player"))?;
+
+    world.send_event(GainXp { target, amount });
+    Ok(String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_modifiers_apply_before_percent() {
+        let mut stats = Stats::new([(Stat::MoveSpeed, 50.)]);
+        stats.set_modifiers(
+            ModifierSource::Equipment,
+            vec![
+                StatModifier::flat(Stat::MoveSpeed, 10.),
+                StatModifier::percent(Stat::MoveSpeed, 0.5),
+            ],
+        );
+        stats.set_modifiers(
+            ModifierSource::Status,
+            vec![StatModifier::percent(Stat::MoveSpeed, -0.25)],
+        );
+
+        assert_eq!(stats.get(Stat::MoveSpeed), 75.);
+        assert_eq!(stats.get(Stat::Gravity), 0.);
+        assert_eq!(stats.damage(4), 4);
+    }
+
+    #[test]
+    fn experience_carries_over_between_levels() {
+        let mut experience = Experience::default();
+        assert_eq!(experience.gain(15), 0);
+        assert_eq!(experience.gain(50), 2);
+        assert_eq!(experience, Experience { level: 3, xp: 5 });
+        assert_eq!(
+            experience.modifiers(),
+            vec![
+                StatModifier::flat(Stat::MaxHealth, 2.),
+                StatModifier::percent(Stat::Damage, 0.2),
+            ]
+        );
+    }
+}
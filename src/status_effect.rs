@@ -0,0 +1,387 @@
+use {
+    super::{
+        console::{self, AddConsoleCommand},
+        game_state::GameState,
+        health::DamageEvent,
+        physics::{self, NetDirection},
+        player::{self, Player},
+        ron_asset,
+        stats::{self, ModifierSource, Stat, StatModifier, Stats},
+        time_control::TimeScale,
+    },
+    bevy::prelude::*,
+    serde::Deserialize,
+    std::{str::FromStr, time::Duration},
+};
+
+pub struct StatusEffectPlugin;
+
+impl Plugin for StatusEffectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ApplyStatus>()
+            .add_systems(
+                FixedUpdate,
+                cancel_stunned_jumps
+                    .after(player::discrete_player_input)
+                    .before(player::player_movement)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (apply_statuses, tick_statuses, apply_movement_effects)
+                    .chain()
+                    .after(player::player_movement)
+                    .before(stats::apply_stats)
+                    .before(physics::apply_forces)
+                    .distributive_run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                tint_afflicted_sprites.run_if(in_state(GameState::Playing)),
+            )
+            .add_console_command(
+                "status",
+                "status <poison|burn|slow|stun> <seconds> [stacks]: afflict the player",
+                afflict_player,
+            );
+    }
+}
+
+const SLOW_PERCENT: f32 = -0.5;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusKind {
+    Poison,
+    Burn,
+    Slow,
+    Stun,
+}
+
+enum Stacking {
+    Refresh,
+    Extend,
+    Intensify { max_stacks: u32 },
+}
+
+impl StatusKind {
+    fn stacking(self) -> Stacking {
+        match self {
+            Self::Poison => Stacking::Intensify { max_stacks: 5 },
+            Self::Burn | Self::Slow => Stacking::Refresh,
+            Self::Stun => Stacking::Extend,
+        }
+    }
+
+    fn damage_interval(self) -> Option<f32> {
+        match self {
+            Self::Poison => Some(1.),
+            Self::Burn => Some(0.5),
+            Self::Slow | Self::Stun => None,
+        }
+    }
+
+    fn tint(self) -> Color {
+        match self {
+            Self::Poison => Color::rgb(0.5, 1., 0.4),
+            Self::Burn => Color::rgb(1., 0.55, 0.3),
+            Self::Slow => Color::rgb(0.55, 0.75, 1.),
+            Self::Stun => Color::rgb(1., 1., 0.45),
+        }
+    }
+}
+
+impl FromStr for StatusKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "poison" => Ok(Self::Poison),
+            "burn" => Ok(Self::Burn),
+            "slow" => Ok(Self::Slow),
+            "stun" => Ok(Self::Stun),
+            _ => Err(format!("unknown status `{s}`")),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub duration: f32,
+    #[serde(default = "ron_asset::one")]
+    pub stacks: u32,
+}
+
+struct ActiveStatus {
+    kind: StatusKind,
+    stacks: u32,
+    duration: Timer,
+    damage_tick: Option<Timer>,
+}
+
+#[derive(Component, Default)]
+pub struct StatusEffects {
+    active: Vec<ActiveStatus>,
+}
+
+impl StatusEffects {
+    pub fn apply(&mut self, effect: StatusEffect) {
+        let duration = Duration::from_secs_f32(effect.duration);
+        let Some(active) = self
+            .active
+            .iter_mut()
+            .find(|active| active.kind == effect.kind)
+        else {
+            let max_stacks = match effect.kind.stacking() {
+                Stacking::Intensify { max_stacks } => max_stacks,
+                _ => 1,
+            };
+            self.active.push(ActiveStatus {
+                kind: effect.kind,
+                stacks: effect.stacks.clamp(1, max_stacks),
+                duration: Timer::new(duration, TimerMode::Once),
+                damage_tick: effect
+                    .kind
+                    .damage_interval()
+                    .map(|interval| Timer::from_seconds(interval, TimerMode::Repeating)),
+            });
+            return;
+        };
+
+        match effect.kind.stacking() {
+            Stacking::Refresh => {
+                let remaining = active.duration.remaining().max(duration);
+                active.duration = Timer::new(remaining, TimerMode::Once);
+            }
+            Stacking::Extend => {
+                let extended = active.duration.duration() + duration;
+                active.duration.set_duration(extended);
+            }
+            Stacking::Intensify { max_stacks } => {
+                active.stacks = (active.stacks + effect.stacks).min(max_stacks);
+                let remaining = active.duration.remaining().max(duration);
+                active.duration = Timer::new(remaining, TimerMode::Once);
+            }
+        }
+    }
+
+    pub fn tick(&mut self, dt: Duration) -> u32 {
+        let mut damage = 0;
+        for active in self.active.iter_mut() {
+            active.duration.tick(dt);
+            if let Some(damage_tick) = active.damage_tick.as_mut() {
+                damage_tick.tick(dt);
+                damage += damage_tick.times_finished_this_tick() * active.stacks;
+            }
+        }
+        self.active.retain(|active| !active.duration.finished());
+        damage
+    }
+
+    pub fn stacks(&self, kind: StatusKind) -> u32 {
+        self.active
+            .iter()
+            .find(|active| active.kind == kind)
+            .map_or(0, |active| active.stacks)
+    }
+
+    pub fn stunned(&self) -> bool {
+        self.stacks(StatusKind::Stun) > 0
+    }
+
+    pub fn modifiers(&self) -> Vec<StatModifier> {
+        if self.stacks(StatusKind::Slow) == 0 {
+            return Vec::new();
+        }
+        vec![
+            StatModifier::percent(Stat::MoveSpeed, SLOW_PERCENT),
+            StatModifier::percent(Stat::Acceleration, SLOW_PERCENT),
+        ]
+    }
+
+    pub fn tint(&self) -> Color {
+        if self.active.is_empty() {
+            return Color::WHITE;
+        }
+        let sum = self
+            .active
+            .iter()
+            .map(|active| Vec4::from(active.kind.tint()))
+            .sum::<Vec4>();
+        Color::from(sum / self.active.len() as f32)
+    }
+}
+
+#[derive(Event)]
+pub struct ApplyStatus {
+    pub target: Entity,
+    pub effect: StatusEffect,
+}
+
+fn apply_statuses(
+    mut apply_status_evr: EventReader<ApplyStatus>,
+    mut status_qry: Query<&mut StatusEffects>,
+) {
+    for apply_status in apply_status_evr.read() {
+        if let Ok(mut status_effects) = status_qry.get_mut(apply_status.target) {
+            status_effects.apply(apply_status.effect);
+        }
+    }
+}
+
+fn tick_statuses(
+    time: Res<Time<Fixed>>,
+    mut damage_evw: EventWriter<DamageEvent>,
+    mut status_qry: Query<(Entity, &mut StatusEffects, Option<&TimeScale>)>,
+) {
+    for (target, mut status_effects, time_scale) in status_qry.iter_mut() {
+        if status_effects.active.is_empty() {
+            continue;
+        }
+        let scale = time_scale.map_or(1., |time_scale| time_scale.0);
+        let amount = status_effects.tick(time.timestep().mul_f32(scale));
+        if amount > 0 {
+            damage_evw.send(DamageEvent {
+                target,
+                amount,
+                knockback: Vec2::ZERO,
+            });
+        }
+    }
+}
+
+fn cancel_stunned_jumps(mut player_qry: Query<(&StatusEffects, &mut Player)>) {
+    for (status_effects, mut player) in player_qry.iter_mut() {
+        if status_effects.stunned() && player.can_jump {
+            player.can_jump = false;
+        }
+    }
+}
+
+fn apply_movement_effects(
+    mut status_qry: Query<(&StatusEffects, &mut NetDirection, Option<&mut Stats>)>,
+) {
+    for (status_effects, mut net_dir, stats) in status_qry.iter_mut() {
+        if status_effects.stunned() {
+            net_dir.x = 0;
+        }
+
+        let Some(mut stats) = stats else {
+            continue;
+        };
+        let modifiers = status_effects.modifiers();
+        if stats.modifiers(ModifierSource::Status) != modifiers.as_slice() {
+            stats.set_modifiers(ModifierSource::Status, modifiers);
+        }
+    }
+}
+
+fn tint_afflicted_sprites(
+    mut sprite_qry: Query<(&StatusEffects, &mut Sprite)>,
+    mut tex_atlas_sprite_qry: Query<(&StatusEffects, &mut TextureAtlasSprite)>,
+) {
+    for (status_effects, mut sprite) in sprite_qry.iter_mut() {
+        let tint = status_effects.tint();
+        if sprite.color != tint {
+            sprite.color = tint;
+        }
+    }
+    for (status_effects, mut sprite) in tex_atlas_sprite_qry.iter_mut() {
+        let tint = status_effects.tint();
+        if sprite.color != tint {
+            sprite.color = tint;
+        }
+    }
+}
+
+fn afflict_player(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let usage = || String::from("usage: status <kind> <seconds> [stacks]");
+    let (kind, args) = args.split_first().ok_or_else(usage)?;
+    let kind = kind.parse::<StatusKind>()?;
+    let (duration, stacks) = match console::parse_args::<f32>(args)?[..] {
+        [duration] => (duration, 1),
+        [duration, stacks] => (duration, stacks as u32),
+        _ => return Err(usage()),
+    };
+    let target = world
+        .query_filtered::<Entity, With<Player>>()
+        .get_single(world)
+        .map_err(|_| String::from("no player"))?;
+
+    world.send_event(ApplyStatus {
+        target,
+        effect: StatusEffect {
+            kind,
+            duration,
+            stacks,
+        },
+    });
+    Ok(String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{physics::TerminalVelocity, player::PlayerAction, simulation::Simulation},
+        bevy_rapier2d::prelude::Velocity,
+    };
+
+    fn effect(kind: StatusKind, duration: f32, stacks: u32) -> StatusEffect {
+        StatusEffect {
+            kind,
+            duration,
+            stacks,
+        }
+    }
+
+    #[test]
+    fn stacking_rules_per_kind() {
+        let mut status_effects = StatusEffects::default();
+        status_effects.apply(effect(StatusKind::Poison, 3., 2));
+        status_effects.apply(effect(StatusKind::Poison, 1., 4));
+        status_effects.apply(effect(StatusKind::Stun, 1., 1));
+        status_effects.apply(effect(StatusKind::Stun, 1., 1));
+
+        assert_eq!(status_effects.stacks(StatusKind::Poison), 5);
+        assert_eq!(status_effects.tick(Duration::from_secs(1)), 5);
+        assert_eq!(status_effects.stacks(StatusKind::Stun), 1);
+        assert_eq!(status_effects.tick(Duration::from_millis(1500)), 5);
+        assert_eq!(status_effects.stacks(StatusKind::Stun), 0);
+        assert_eq!(status_effects.tick(Duration::from_secs(1)), 5);
+        assert_eq!(status_effects.stacks(StatusKind::Poison), 0);
+        assert_eq!(status_effects.tint(), Color::WHITE);
+    }
+
+    #[test]
+    fn stun_and_slow_hook_into_movement() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let player_id = sim
+            .app
+            .world
+            .query_filtered::<Entity, With<Player>>()
+            .single(&sim.app.world);
+        let start_x = sim.player::<Transform>().translation.x;
+
+        sim.app.world.send_event(ApplyStatus {
+            target: player_id,
+            effect: effect(StatusKind::Stun, 0.5, 1),
+        });
+        sim.step_n(16, &[PlayerAction::MoveRight]);
+        assert_eq!(sim.player::<Transform>().translation.x, start_x);
+        sim.step(&[PlayerAction::Jump]);
+        sim.step_n(4, &[]);
+        assert!(sim.player::<Velocity>().linvel.y <= 0.);
+        sim.step_n(12, &[]);
+
+        sim.app.world.send_event(ApplyStatus {
+            target: player_id,
+            effect: effect(StatusKind::Slow, 1., 1),
+        });
+        sim.step(&[]);
+        assert_eq!(sim.player::<TerminalVelocity>().x, 25.);
+        sim.step_n(80, &[]);
+        assert_eq!(sim.player::<TerminalVelocity>().x, 50.);
+    }
+}
@@ -1,5 +1,17 @@
 use {
-    super::game_state::GameState, bevy::prelude::*, bevy_ecs_tilemap::prelude::*,
+    super::{
+        console::{self, AddConsoleCommand},
+        floor::{self, FloorState},
+        game_state::{self, GameState},
+        physics::{ACTOR_GROUP, TILE_GROUP},
+        player::PlayerCamera,
+        rng::RunSeed,
+    },
+    bevy::{
+        prelude::*,
+        utils::{HashMap, HashSet},
+    },
+    bevy_ecs_tilemap::prelude::*,
     bevy_rapier2d::prelude::*,
 };
 
@@ -7,48 +19,589 @@ pub struct TilePlugin;
 
 impl Plugin for TilePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Playing), spawn_tiles);
+        app.init_resource::<TileWorld>()
+            .init_resource::<Chunks>()
+            .add_event::<DamageTile>()
+            .add_event::<PlaceTile>()
+            .add_event::<TileChanged>()
+            .add_systems(Startup, load_tileset)
+            .add_systems(game_state::END_RUN, despawn_chunks)
+            .add_systems(Update, edit_tiles.run_if(in_state(GameState::Playing)))
+            .add_systems(
+                PostUpdate,
+                (stream_chunks, sync_chunks)
+                    .chain()
+                    .after(floor::stream_rooms)
+                    .before(PhysicsSet::SyncBackend)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_console_command("regen", "regen [seed]: regenerate the floor", regenerate)
+            .add_console_command("dig", "dig <x> <y> [amount]: damage a tile", dig)
+            .add_console_command("place", "place <x> <y> [texture]: place a tile", place);
     }
 }
 
 #[derive(Component)]
 pub struct Tile;
 
-fn spawn_tiles(
-    mut cmds: Commands,
-    asset_server: Res<AssetServer>,
-    mut tex_atlases: ResMut<Assets<TextureAtlas>>,
-) {
-    let tilemap_size = TilemapSize { x: 32, y: 32 };
-    let tilemap_id = cmds.spawn_empty().id();
-    let mut tile_storage = TileStorage::empty(tilemap_size);
+pub const TILEMAP_SIZE: TilemapSize = TilemapSize { x: 32, y: 32 };
+pub const TILE_SIZE: TilemapTileSize = TilemapTileSize { x: 16., y: 16. };
+pub const CHUNK_SIZE: TilemapSize = TilemapSize { x: 16, y: 16 };
+const LOAD_DISTANCE: f32 = 512.;
+const UNLOAD_DISTANCE: f32 = 768.;
+const TILE_DURABILITY: u32 = 3;
+const DAMAGE_SHADE: f32 = 0.5;
+const DOORWAY_HEIGHT: u32 = 3;
+const SHAFT_WIDTH: u32 = 2;
+const LEDGE_SPACING: u32 = 2;
+const LEDGE_WIDTH: u32 = 2;
+
+pub fn generate_tiles(tilemap_size: TilemapSize, doorways: &[IVec2]) -> Vec<TilePos> {
+    let mut tiles = Vec::new();
+    let shaft_x = tilemap_size.x / 2 - SHAFT_WIDTH / 2;
+    let shaft = shaft_x..shaft_x + SHAFT_WIDTH;
+    let doorway_rows = 1..DOORWAY_HEIGHT + 1;
+    let is_doorway = |x: u32, y: u32| {
+        doorways.iter().any(|&dir| match (dir.x, dir.y) {
+            (-1, 0) => x == 0 && doorway_rows.contains(&y),
+            (1, 0) => x == tilemap_size.x - 1 && doorway_rows.contains(&y),
+            (0, 1) => y == tilemap_size.y - 1 && shaft.contains(&x),
+            (0, -1) => y == 0 && shaft.contains(&x),
+            _ => false,
+        })
+    };
 
     for y in 0..tilemap_size.y {
         for x in 0..tilemap_size.x {
-            if (y == 0 || y == tilemap_size.y - 1) || (x == 0 || x == tilemap_size.x - 1) {
-                let tile_pos = TilePos { x, y };
-                let tile_id = cmds
-                    .spawn(TileBundle {
-                        position: tile_pos,
-                        tilemap_id: TilemapId(tilemap_id),
-                        ..default()
-                    })
-                    .id();
-                tile_storage.set(&tile_pos, tile_id);
+            if ((y == 0 || y == tilemap_size.y - 1) || (x == 0 || x == tilemap_size.x - 1))
+                && !is_doorway(x, y)
+            {
+                tiles.push(TilePos { x, y });
             }
         }
     }
-    let tile_size = TilemapTileSize { x: 16., y: 16. };
-    let grid_size = TilemapGridSize::from(tile_size);
+
+    if doorways.contains(&IVec2::Y) {
+        for (i, y) in (LEDGE_SPACING..tilemap_size.y - 1)
+            .step_by(LEDGE_SPACING as usize)
+            .enumerate()
+        {
+            let start_x = if i % 2 == 0 {
+                shaft.start - LEDGE_WIDTH
+            } else {
+                shaft.end
+            };
+            tiles.extend((start_x..start_x + LEDGE_WIDTH).map(|x| TilePos { x, y }));
+        }
+    }
+    tiles
+}
+
+#[derive(Resource, Default)]
+pub struct TileWorld {
+    tiles: HashMap<IVec2, TileTextureIndex>,
+    damage: HashMap<IVec2, u32>,
+    changed: HashSet<IVec2>,
+}
+
+impl TileWorld {
+    pub fn get(&self, tile: IVec2) -> Option<TileTextureIndex> {
+        self.tiles.get(&tile).copied()
+    }
+
+    pub fn set(&mut self, tile: IVec2, tex_idx: TileTextureIndex) {
+        if self.tiles.insert(tile, tex_idx) != Some(tex_idx) {
+            self.changed.insert(tile);
+        }
+    }
+
+    pub fn damage(&mut self, tile: IVec2, amount: u32) -> bool {
+        if !self.tiles.contains_key(&tile) {
+            return false;
+        }
+        let damage = self.damage.entry(tile).or_default();
+        *damage = damage.saturating_add(amount);
+        if *damage < TILE_DURABILITY {
+            self.changed.insert(tile);
+            return false;
+        }
+        self.remove(tile).is_some()
+    }
+
+    pub fn remove(&mut self, tile: IVec2) -> Option<TileTextureIndex> {
+        self.damage.remove(&tile);
+        let tex_idx = self.tiles.remove(&tile)?;
+        self.changed.insert(tile);
+        Some(tex_idx)
+    }
+
+    pub fn color(&self, tile: IVec2) -> TileColor {
+        let damage = self.damage.get(&tile).copied().unwrap_or_default();
+        let shade = 1. - DAMAGE_SHADE * damage as f32 / TILE_DURABILITY as f32;
+        TileColor(Color::rgb(shade, shade, shade))
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = IVec2> + '_ {
+        self.tiles.keys().copied()
+    }
+
+    pub fn changed(&self) -> impl Iterator<Item = IVec2> + '_ {
+        self.changed.iter().copied()
+    }
+
+    pub fn clear(&mut self) {
+        self.changed.extend(self.tiles.keys());
+        self.tiles.clear();
+        self.damage.clear();
+    }
+
+    pub fn tiles_in(
+        &self,
+        min: IVec2,
+        size: TilemapSize,
+    ) -> impl Iterator<Item = (IVec2, TileTextureIndex)> + '_ {
+        (0..size.y as i32)
+            .flat_map(move |y| (0..size.x as i32).map(move |x| min + IVec2::new(x, y)))
+            .filter_map(|tile| Some((tile, self.get(tile)?)))
+    }
+}
+
+#[derive(Resource)]
+pub struct Tileset(pub Handle<Image>);
+
+#[derive(Resource, Default)]
+pub struct Chunks(HashMap<IVec2, Entity>);
+
+#[derive(Event)]
+pub struct DamageTile {
+    pub tile: IVec2,
+    pub amount: u32,
+}
+
+#[derive(Event)]
+pub struct PlaceTile {
+    pub tile: IVec2,
+    pub tex_idx: TileTextureIndex,
+}
+
+#[derive(Event, Debug, PartialEq)]
+pub struct TileChanged {
+    pub tile: IVec2,
+    pub solid: bool,
+}
+
+pub fn tile_at(pos: Vec2) -> IVec2 {
+    (pos / Vec2::from(TILE_SIZE)).floor().as_ivec2()
+}
+
+pub fn tile_center(tile: IVec2) -> Vec2 {
+    (tile.as_vec2() + 0.5) * Vec2::from(TILE_SIZE)
+}
+
+pub fn chunk_of(tile: IVec2) -> IVec2 {
+    tile.div_euclid(chunk_dims())
+}
+
+fn chunk_dims() -> IVec2 {
+    IVec2::new(CHUNK_SIZE.x as i32, CHUNK_SIZE.y as i32)
+}
+
+fn chunk_distance(chunk: IVec2, pos: Vec2) -> f32 {
+    let min = (chunk * chunk_dims()).as_vec2() * Vec2::from(TILE_SIZE);
+    let max = min + chunk_dims().as_vec2() * Vec2::from(TILE_SIZE);
+    pos.distance(pos.clamp(min, max))
+}
+
+fn spawn_tile(
+    cmds: &mut Commands,
+    tilemap_id: Entity,
+    tile_pos: TilePos,
+    tile: IVec2,
+    tex_idx: TileTextureIndex,
+    color: TileColor,
+) -> Entity {
+    cmds.spawn((
+        Tile,
+        TileBundle {
+            position: tile_pos,
+            tilemap_id: TilemapId(tilemap_id),
+            texture_index: tex_idx,
+            color,
+            ..default()
+        },
+        TransformBundle::from_transform(Transform::from_translation(tile_center(tile).extend(0.))),
+        Collider::cuboid(TILE_SIZE.x / 2., TILE_SIZE.y / 2.),
+        CollisionGroups::new(TILE_GROUP, Group::ALL),
+    ))
+    .id()
+}
+
+fn load_tileset(mut cmds: Commands, asset_server: Res<AssetServer>) {
+    cmds.insert_resource(Tileset(asset_server.load("tile.png")));
+}
+
+fn spawn_chunk(
+    cmds: &mut Commands,
+    tileset: &Tileset,
+    tile_world: &TileWorld,
+    chunk: IVec2,
+) -> Entity {
+    let min = chunk * chunk_dims();
+    let tilemap_id = cmds.spawn(Name::new(format!("Chunk {chunk}"))).id();
+    let mut tile_storage = TileStorage::empty(CHUNK_SIZE);
+
+    for (tile, tex_idx) in tile_world.tiles_in(min, CHUNK_SIZE) {
+        let local = tile - min;
+        let tile_pos = TilePos {
+            x: local.x as u32,
+            y: local.y as u32,
+        };
+        let color = tile_world.color(tile);
+        let tile_id = spawn_tile(cmds, tilemap_id, tile_pos, tile, tex_idx, color);
+        tile_storage.set(&tile_pos, tile_id);
+    }
 
     cmds.entity(tilemap_id).insert(TilemapBundle {
-        grid_size,
-        size: tilemap_size,
+        grid_size: TilemapGridSize::from(TILE_SIZE),
+        size: CHUNK_SIZE,
         storage: tile_storage,
-        texture: TilemapTexture::Single(asset_server.load("tile.png")),
-        tile_size,
-        transform: get_tilemap_center_transform(&tilemap_size, &grid_size, &default(), 0.),
+        texture: TilemapTexture::Single(tileset.0.clone()),
+        tile_size: TILE_SIZE,
+        transform: Transform::from_translation(tile_center(min).extend(0.)),
         spacing: TilemapSpacing::zero(),
         ..default()
     });
+    tilemap_id
+}
+
+fn despawn_chunk(cmds: &mut Commands, tilemap_id: Entity, tile_storage: &TileStorage) {
+    for tile_id in tile_storage.iter().flatten() {
+        cmds.entity(*tile_id).despawn_recursive();
+    }
+    cmds.entity(tilemap_id).despawn_recursive();
+}
+
+pub fn stream_chunks(
+    mut cmds: Commands,
+    tileset: Res<Tileset>,
+    tile_world: Res<TileWorld>,
+    mut chunks: ResMut<Chunks>,
+    cam_qry: Query<&GlobalTransform, With<PlayerCamera>>,
+    tilemap_qry: Query<&TileStorage>,
+) {
+    let Ok(cam_glob_xform) = cam_qry.get_single() else {
+        return;
+    };
+    let center = cam_glob_xform.translation().truncate();
+
+    chunks.0.retain(|&chunk, &mut tilemap_id| {
+        if chunk_distance(chunk, center) <= UNLOAD_DISTANCE {
+            return true;
+        }
+        if let Ok(tile_storage) = tilemap_qry.get(tilemap_id) {
+            despawn_chunk(&mut cmds, tilemap_id, tile_storage);
+        }
+        false
+    });
+
+    let center_chunk = chunk_of(tile_at(center));
+    let radius = (LOAD_DISTANCE / (CHUNK_SIZE.x as f32 * TILE_SIZE.x)).ceil() as i32;
+    for y in -radius..=radius {
+        for x in -radius..=radius {
+            let chunk = center_chunk + IVec2::new(x, y);
+            if chunks.0.contains_key(&chunk) || chunk_distance(chunk, center) > LOAD_DISTANCE {
+                continue;
+            }
+            let tilemap_id = spawn_chunk(&mut cmds, &tileset, &tile_world, chunk);
+            chunks.0.insert(chunk, tilemap_id);
+        }
+    }
+}
+
+fn despawn_chunks(
+    mut cmds: Commands,
+    mut tile_world: ResMut<TileWorld>,
+    mut chunks: ResMut<Chunks>,
+    tilemap_qry: Query<&TileStorage>,
+) {
+    for (_, tilemap_id) in chunks.0.drain() {
+        if let Ok(tile_storage) = tilemap_qry.get(tilemap_id) {
+            despawn_chunk(&mut cmds, tilemap_id, tile_storage);
+        }
+    }
+    *tile_world = TileWorld::default();
+}
+
+pub fn sync_chunks(
+    mut cmds: Commands,
+    mut tile_world: ResMut<TileWorld>,
+    chunks: Res<Chunks>,
+    mut tile_changed_evw: EventWriter<TileChanged>,
+    mut tilemap_qry: Query<&mut TileStorage>,
+) {
+    if tile_world.changed.is_empty() {
+        return;
+    }
+    let changed = tile_world.changed.drain().collect::<Vec<_>>();
+
+    for tile in changed {
+        tile_changed_evw.send(TileChanged {
+            tile,
+            solid: tile_world.get(tile).is_some(),
+        });
+        let chunk = chunk_of(tile);
+        let Some(&tilemap_id) = chunks.0.get(&chunk) else {
+            continue;
+        };
+        let Ok(mut tile_storage) = tilemap_qry.get_mut(tilemap_id) else {
+            continue;
+        };
+        let local = tile - chunk * chunk_dims();
+        let tile_pos = TilePos {
+            x: local.x as u32,
+            y: local.y as u32,
+        };
+
+        match (tile_storage.get(&tile_pos), tile_world.get(tile)) {
+            (Some(tile_id), Some(tex_idx)) => {
+                cmds.entity(tile_id)
+                    .insert((tex_idx, tile_world.color(tile)));
+            }
+            (Some(tile_id), None) => {
+                cmds.entity(tile_id).despawn_recursive();
+                tile_storage.remove(&tile_pos);
+            }
+            (None, Some(tex_idx)) => {
+                let color = tile_world.color(tile);
+                let tile_id = spawn_tile(&mut cmds, tilemap_id, tile_pos, tile, tex_idx, color);
+                tile_storage.set(&tile_pos, tile_id);
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+fn edit_tiles(
+    rapier_ctx: Res<RapierContext>,
+    mut tile_world: ResMut<TileWorld>,
+    mut damage_tile_evr: EventReader<DamageTile>,
+    mut place_tile_evr: EventReader<PlaceTile>,
+) {
+    for damage_tile in damage_tile_evr.read() {
+        tile_world.damage(damage_tile.tile, damage_tile.amount);
+    }
+    for place_tile in place_tile_evr.read() {
+        if tile_world.get(place_tile.tile).is_some() {
+            continue;
+        }
+        let mut blocked = false;
+        rapier_ctx.intersections_with_shape(
+            tile_center(place_tile.tile),
+            0.,
+            &Collider::cuboid(TILE_SIZE.x / 2. - 0.5, TILE_SIZE.y / 2. - 0.5),
+            QueryFilter::new()
+                .exclude_sensors()
+                .groups(CollisionGroups::new(Group::ALL, ACTOR_GROUP)),
+            |_| {
+                blocked = true;
+                false
+            },
+        );
+        if !blocked {
+            tile_world.set(place_tile.tile, place_tile.tex_idx);
+        }
+    }
+}
+
+fn dig(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let (x, y, amount) = match console::parse_args::<i32>(args)?[..] {
+        [x, y] => (x, y, TILE_DURABILITY as i32),
+        [x, y, amount] if amount > 0 => (x, y, amount),
+        _ => return Err(String::from("usage: dig <x> <y> [amount]")),
+    };
+    world.send_event(DamageTile {
+        tile: IVec2::new(x, y),
+        amount: amount as u32,
+    });
+    Ok(String::new())
+}
+
+fn place(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let (x, y, texture) = match console::parse_args::<i32>(args)?[..] {
+        [x, y] => (x, y, 0),
+        [x, y, texture] if texture >= 0 => (x, y, texture),
+        _ => return Err(String::from("usage: place <x> <y> [texture]")),
+    };
+    world.send_event(PlaceTile {
+        tile: IVec2::new(x, y),
+        tex_idx: TileTextureIndex(texture as u32),
+    });
+    Ok(String::new())
+}
+
+fn regenerate(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let seed = match console::parse_args::<u64>(args)?[..] {
+        [] => world.resource::<RunSeed>().0,
+        [seed] => seed,
+        _ => return Err(String::from("usage: regen [seed]")),
+    };
+    world.insert_resource(RunSeed(seed));
+    world.resource_mut::<FloorState>().reset();
+    world.resource_mut::<TileWorld>().clear();
+    Ok(format!("regenerated floor with seed {seed}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::simulation::Simulation};
+
+    #[test]
+    fn tile_coordinates_span_chunk_boundaries() {
+        assert_eq!(tile_at(Vec2::new(-0.5, 15.9)), IVec2::new(-1, 0));
+        assert_eq!(tile_center(IVec2::new(-1, 0)), Vec2::new(-8., 8.));
+        assert_eq!(chunk_of(IVec2::new(-1, 15)), IVec2::new(-1, 0));
+        assert_eq!(chunk_of(IVec2::new(16, -17)), IVec2::new(1, -2));
+    }
+
+    #[test]
+    fn chunks_stream_with_the_camera_and_follow_tile_changes() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let loaded_tiles = |sim: &mut Simulation| {
+            sim.app
+                .world
+                .query_filtered::<&Transform, With<Tile>>()
+                .iter(&sim.app.world)
+                .map(|xform| tile_at(xform.translation.truncate()))
+                .collect::<HashSet<_>>()
+        };
+        let assert_streamed = |sim: &mut Simulation| {
+            let cam_pos = sim
+                .app
+                .world
+                .query_filtered::<&GlobalTransform, With<PlayerCamera>>()
+                .single(&sim.app.world)
+                .translation()
+                .truncate();
+            let chunks = &sim.app.world.resource::<Chunks>().0;
+            assert!(chunks
+                .keys()
+                .all(|&chunk| chunk_distance(chunk, cam_pos) <= UNLOAD_DISTANCE));
+            assert!(chunks.contains_key(&chunk_of(tile_at(cam_pos))));
+            let chunk_count = chunks.len();
+            let tilemap_count = sim
+                .app
+                .world
+                .query::<&TileStorage>()
+                .iter(&sim.app.world)
+                .count();
+            assert_eq!(tilemap_count, chunk_count);
+        };
+        assert_streamed(&mut sim);
+
+        let floor_tile = IVec2::new(0, -16);
+        assert!(loaded_tiles(&mut sim).contains(&floor_tile));
+        let mut tile_world = sim.app.world.resource_mut::<TileWorld>();
+        tile_world.tiles.remove(&floor_tile);
+        tile_world.changed.insert(floor_tile);
+        tile_world.set(IVec2::new(2, -10), TileTextureIndex(0));
+        sim.step(&[]);
+        let tiles = loaded_tiles(&mut sim);
+        assert!(!tiles.contains(&floor_tile));
+        assert!(tiles.contains(&IVec2::new(2, -10)));
+
+        let far_room = *sim
+            .app
+            .world
+            .resource::<floor::FloorLayout>()
+            .rooms
+            .keys()
+            .max_by_key(|room| room.length_squared())
+            .unwrap();
+        let mut player_xform = sim
+            .app
+            .world
+            .query_filtered::<&mut Transform, With<crate::player::Player>>()
+            .single_mut(&mut sim.app.world);
+        player_xform.translation = floor::room_origin(far_room).extend(player_xform.translation.z);
+        sim.step_n(60, &[]);
+        assert_streamed(&mut sim);
+        assert!(!sim
+            .app
+            .world
+            .resource::<Chunks>()
+            .0
+            .contains_key(&IVec2::new(-1, -1)));
+    }
+
+    #[test]
+    fn tiles_break_and_place_at_runtime() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let tile_color = |sim: &mut Simulation, tile: IVec2| {
+            sim.app
+                .world
+                .query_filtered::<(&Transform, &TileColor), With<Tile>>()
+                .iter(&sim.app.world)
+                .find(|(xform, _)| tile_at(xform.translation.truncate()) == tile)
+                .map(|(_, color)| color.0)
+        };
+        let changes = |sim: &mut Simulation| {
+            sim.app
+                .world
+                .resource_mut::<Events<TileChanged>>()
+                .drain()
+                .collect::<Vec<_>>()
+        };
+        let player_tile = tile_at(sim.player::<Transform>().translation.truncate());
+        let floor_tile = (1..TILEMAP_SIZE.y as i32)
+            .map(|depth| player_tile - IVec2::Y * depth)
+            .find(|&tile| sim.app.world.resource::<TileWorld>().get(tile).is_some())
+            .unwrap();
+        changes(&mut sim);
+
+        sim.app.world.send_event(DamageTile {
+            tile: floor_tile,
+            amount: 1,
+        });
+        sim.step(&[]);
+        assert!(tile_color(&mut sim, floor_tile).is_some_and(|color| color.r() < 1.));
+        assert_eq!(
+            changes(&mut sim),
+            vec![TileChanged {
+                tile: floor_tile,
+                solid: true,
+            }]
+        );
+
+        sim.app.world.send_event(DamageTile {
+            tile: floor_tile,
+            amount: TILE_DURABILITY,
+        });
+        sim.step(&[]);
+        assert_eq!(tile_color(&mut sim, floor_tile), None);
+        assert_eq!(sim.app.world.resource::<TileWorld>().get(floor_tile), None);
+        assert!(changes(&mut sim).contains(&TileChanged {
+            tile: floor_tile,
+            solid: false,
+        }));
+
+        let above = (2..)
+            .map(|height| player_tile + IVec2::Y * height)
+            .find(|&tile| sim.app.world.resource::<TileWorld>().get(tile).is_none())
+            .unwrap();
+        for tile in [player_tile, above] {
+            sim.app.world.send_event(PlaceTile {
+                tile,
+                tex_idx: TileTextureIndex(0),
+            });
+        }
+        sim.step(&[]);
+        assert_eq!(sim.app.world.resource::<TileWorld>().get(player_tile), None);
+        assert_eq!(tile_color(&mut sim, above), Some(Color::WHITE));
+        assert!(changes(&mut sim).contains(&TileChanged {
+            tile: above,
+            solid: true,
+        }));
+    }
 }
@@ -0,0 +1,157 @@
+use {
+    super::{
+        console::{self, AddConsoleCommand},
+        game_state::GameState,
+        health::Died,
+        player::Player,
+        settings::Settings,
+    },
+    bevy::prelude::*,
+};
+
+pub struct TimeControlPlugin;
+
+impl Plugin for TimeControlPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeControl>()
+            .add_systems(
+                Update,
+                slow_motion_on_kill.run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(Last, apply_time_scale)
+            .add_console_command(
+                "timescale",
+                "timescale [player] <scale>: set the global or player time scale",
+                set_time_scale,
+            );
+    }
+}
+
+const KILL_SLOW_MOTION_SCALE: f32 = 0.3;
+const KILL_SLOW_MOTION_SECONDS: f32 = 0.6;
+
+#[derive(Component, Clone, Copy, Deref, DerefMut)]
+pub struct TimeScale(pub f32);
+
+struct TimeEffect {
+    scale: f32,
+    timer: Timer,
+}
+
+#[derive(Resource)]
+pub struct TimeControl {
+    pub base_scale: f32,
+    effects: Vec<TimeEffect>,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        Self {
+            base_scale: 1.,
+            effects: Vec::new(),
+        }
+    }
+}
+
+impl TimeControl {
+    pub fn hitstop(&mut self, seconds: f32) {
+        self.slow_motion(0., seconds);
+    }
+
+    pub fn slow_motion(&mut self, scale: f32, seconds: f32) {
+        if seconds > 0. {
+            self.effects.push(TimeEffect {
+                scale: scale.max(0.),
+                timer: Timer::from_seconds(seconds, TimerMode::Once),
+            });
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.effects
+            .iter()
+            .map(|effect| effect.scale)
+            .fold(self.base_scale, f32::min)
+    }
+}
+
+fn apply_time_scale(
+    real_time: Res<Time<Real>>,
+    settings: Res<Settings>,
+    mut time_control: ResMut<TimeControl>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    let dt = real_time.delta();
+    time_control
+        .effects
+        .retain_mut(|effect| !effect.timer.tick(dt).finished());
+
+    let scale = if settings.accessibility.reduce_motion {
+        time_control.base_scale
+    } else {
+        time_control.scale()
+    };
+    if virtual_time.relative_speed() != scale {
+        virtual_time.set_relative_speed(scale);
+    }
+}
+
+fn slow_motion_on_kill(
+    mut died_evr: EventReader<Died>,
+    mut time_control: ResMut<TimeControl>,
+    player_qry: Query<(), With<Player>>,
+) {
+    for died in died_evr.read() {
+        if !player_qry.contains(died.entity) {
+            time_control.slow_motion(KILL_SLOW_MOTION_SCALE, KILL_SLOW_MOTION_SECONDS);
+        }
+    }
+}
+
+fn set_time_scale(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let (player_only, args) = match args {
+        ["player", args @ ..] => (true, args),
+        _ => (false, args),
+    };
+    let &[scale] = &console::parse_args::<f32>(args)?[..] else {
+        return Err(String::from("usage: timescale [player] <scale>"));
+    };
+    if !(0. ..=4.).contains(&scale) {
+        return Err(String::from("scale must be between 0 and 4"));
+    }
+    if !player_only {
+        world.resource_mut::<TimeControl>().base_scale = scale;
+        return Ok(String::new());
+    }
+
+    let player_id = world
+        .query_filtered::<Entity, With<Player>>()
+        .get_single(world)
+        .map_err(|_| String::from("no player"))?;
+    world.entity_mut(player_id).insert(TimeScale(scale));
+    Ok(String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::simulation::Simulation};
+
+    #[test]
+    fn hitstop_freezes_fixed_ticks_then_resumes() {
+        let mut sim = Simulation::new();
+        sim.step_n(8, &[]);
+
+        sim.app.world.resource_mut::<TimeControl>().hitstop(0.1);
+        sim.step(&[]);
+        let frozen_y = sim.player::<Transform>().translation.y;
+        sim.step_n(4, &[]);
+        assert_eq!(sim.player::<Transform>().translation.y, frozen_y);
+
+        sim.step_n(8, &[]);
+        assert!(sim.player::<Transform>().translation.y < frozen_y);
+        assert_eq!(
+            sim.app.world.resource::<Time<Virtual>>().relative_speed(),
+            1.
+        );
+    }
+}
@@ -0,0 +1,894 @@
+use {
+    super::{
+        floor::{self, FloorState, InRoom},
+        game_state::GameState,
+        health::{DamageEvent, Health},
+        physics::{
+            self, Acceleration, Grounded, NetDirection, TerminalVelocity, ACTOR_GROUP, TILE_GROUP,
+        },
+        projectile::{self, Projectile, ProjectileDef},
+        ron_asset::{self, RonAssetPlugin},
+        status_effect::{ApplyStatus, StatusEffect},
+        time_control::TimeScale,
+    },
+    bevy::{prelude::*, sprite::Anchor, utils::HashSet},
+    bevy_rapier2d::prelude::*,
+    serde::Deserialize,
+    std::{f32::consts::TAU, time::Duration},
+};
+
+pub struct TrapPlugin;
+
+impl Plugin for TrapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<TrapLayout>::new(&["traps.ron"]))
+            .add_event::<TriggerTrap>()
+            .add_systems(Startup, load_trap_layout)
+            .add_systems(
+                PostUpdate,
+                spawn_room_traps
+                    .before(floor::stream_rooms)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    press_plates,
+                    tick_traps,
+                    cycle_spikes,
+                    swing_blades,
+                    fire_arrow_traps,
+                    drop_falling_blocks,
+                    crumble_floors,
+                    damage_on_contact,
+                )
+                    .chain()
+                    .after(physics::process_collisions)
+                    .before(physics::apply_forces)
+                    .distributive_run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+const CONTACT_COOLDOWN: f32 = 0.75;
+const CONTACT_KNOCKBACK: f32 = 120.;
+const SPIKE_TRIGGER_SECONDS: f32 = 1.5;
+const FALL_TRIGGER_WIDTH: f32 = 12.;
+const FALL_TRIGGER_RANGE: f32 = 128.;
+const ARROW_SPEED: f32 = 200.;
+const ARROW_LIFETIME: f32 = 3.;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Timing {
+    pub period: f32,
+    pub active: f32,
+    #[serde(default)]
+    pub offset: f32,
+}
+
+impl Timing {
+    pub fn is_active(&self, elapsed: f32) -> bool {
+        (elapsed + self.offset).rem_euclid(self.period) < self.active
+    }
+
+    pub fn started(&self, prev_elapsed: f32, elapsed: f32) -> bool {
+        let cycle = |t: f32| ((t + self.offset) / self.period).floor();
+        cycle(elapsed) > cycle(prev_elapsed)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum TrapKind {
+    Spikes {
+        #[serde(default)]
+        timing: Option<Timing>,
+    },
+    FallingBlock,
+    Blade {
+        length: f32,
+        arc: f32,
+        period: f32,
+    },
+    ArrowTrap {
+        direction: [f32; 2],
+        #[serde(default)]
+        timing: Option<Timing>,
+    },
+    CrumblingFloor {
+        delay: f32,
+        respawn: f32,
+    },
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TrapDef {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub room: [i32; 2],
+    pub pos: [f32; 2],
+    pub kind: TrapKind,
+    #[serde(default = "ron_asset::one")]
+    pub damage: u32,
+    #[serde(default)]
+    pub status: Option<StatusEffect>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PlateDef {
+    #[serde(default)]
+    pub room: [i32; 2],
+    pub pos: [f32; 2],
+    pub traps: Vec<String>,
+}
+
+#[derive(Asset, TypePath, Deserialize)]
+pub struct TrapLayout {
+    #[serde(default)]
+    pub traps: Vec<TrapDef>,
+    #[serde(default)]
+    pub plates: Vec<PlateDef>,
+}
+
+#[derive(Resource, Deref)]
+pub struct TrapLayoutHandle(pub Handle<TrapLayout>);
+
+#[derive(Component)]
+pub struct Trap {
+    pub id: Option<String>,
+    pub damage: u32,
+    pub status: Option<StatusEffect>,
+    pub armed: bool,
+    elapsed: f32,
+    prev_elapsed: f32,
+    cooldowns: Vec<(Entity, f32)>,
+}
+
+impl Trap {
+    fn new(def: &TrapDef, armed: bool) -> Self {
+        Self {
+            id: def.id.clone(),
+            damage: def.damage,
+            status: def.status,
+            armed,
+            elapsed: 0.,
+            prev_elapsed: 0.,
+            cooldowns: Vec::new(),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Spikes {
+    timing: Option<Timing>,
+    triggered: f32,
+}
+
+#[derive(Component)]
+pub struct FallingBlock {
+    pub falling: bool,
+}
+
+#[derive(Component)]
+pub struct Blade {
+    arc: f32,
+    period: f32,
+}
+
+#[derive(Component)]
+pub struct ArrowTrap {
+    direction: Vec2,
+    timing: Option<Timing>,
+}
+
+#[derive(Component)]
+pub struct CrumblingFloor {
+    delay: f32,
+    respawn: f32,
+    timer: Option<Timer>,
+    crumbled: bool,
+}
+
+#[derive(Component)]
+pub struct PressurePlate {
+    pub traps: Vec<String>,
+    pub pressed: bool,
+}
+
+#[derive(Event)]
+pub struct TriggerTrap {
+    pub id: String,
+}
+
+fn load_trap_layout(mut cmds: Commands, asset_server: Res<AssetServer>) {
+    cmds.insert_resource(TrapLayoutHandle(asset_server.load("data/base.traps.ron")));
+}
+
+pub fn spawn_trap(cmds: &mut Commands, asset_server: &AssetServer, def: &TrapDef) -> Entity {
+    let room = IVec2::from_array(def.room);
+    let pos = floor::room_origin(room) + Vec2::from(def.pos);
+    let xform = Transform::from_translation(pos.extend(1.));
+    let sprite = |tex: &str| SpriteBundle {
+        texture: asset_server.load(tex),
+        transform: xform,
+        ..default()
+    };
+
+    match &def.kind {
+        TrapKind::Spikes { timing } => cmds.spawn((
+            Name::new("Spikes"),
+            Trap::new(def, true),
+            Spikes {
+                timing: *timing,
+                triggered: 0.,
+            },
+            sprite("spikes.png"),
+            Collider::cuboid(8., 4.),
+            Sensor,
+        )),
+        TrapKind::FallingBlock => cmds.spawn((
+            Name::new("Falling Block"),
+            Trap::new(def, false),
+            FallingBlock { falling: false },
+            sprite("block.png"),
+            Collider::cuboid(8., 8.),
+            CollisionGroups::new(TILE_GROUP, Group::ALL),
+            KinematicCharacterController {
+                filter_groups: Some(CollisionGroups::new(TILE_GROUP, TILE_GROUP)),
+                ..default()
+            },
+            (
+                Friction::coefficient(0.),
+                Velocity::zero(),
+                TerminalVelocity(Vec2::new(0., 300.)),
+                Acceleration(Vec2::new(0., 600.)),
+                NetDirection::default(),
+                Grounded::default(),
+            ),
+        )),
+        TrapKind::Blade {
+            length,
+            arc,
+            period,
+        } => cmds.spawn((
+            Name::new("Blade"),
+            Trap::new(def, true),
+            Blade {
+                arc: arc.to_radians(),
+                period: *period,
+            },
+            SpriteBundle {
+                sprite: Sprite {
+                    anchor: Anchor::TopCenter,
+                    custom_size: Some(Vec2::new(8., *length)),
+                    ..default()
+                },
+                ..sprite("blade.png")
+            },
+            Collider::capsule(Vec2::new(0., -length / 2.), Vec2::new(0., -length), 4.),
+            Sensor,
+        )),
+        TrapKind::ArrowTrap { direction, timing } => cmds.spawn((
+            Name::new("Arrow Trap"),
+            Trap::new(def, false),
+            ArrowTrap {
+                direction: Vec2::from(*direction).normalize_or_zero(),
+                timing: *timing,
+            },
+            sprite("arrow_trap.png"),
+            Collider::cuboid(8., 8.),
+            CollisionGroups::new(TILE_GROUP, Group::ALL),
+        )),
+        TrapKind::CrumblingFloor { delay, respawn } => cmds.spawn((
+            Name::new("Crumbling Floor"),
+            Trap::new(def, false),
+            CrumblingFloor {
+                delay: *delay,
+                respawn: *respawn,
+                timer: None,
+                crumbled: false,
+            },
+            sprite("crumble.png"),
+            Collider::cuboid(8., 8.),
+            CollisionGroups::new(TILE_GROUP, Group::ALL),
+        )),
+    }
+    .insert(InRoom(room))
+    .id()
+}
+
+pub fn spawn_plate(cmds: &mut Commands, tex: Handle<Image>, def: &PlateDef) -> Entity {
+    let room = IVec2::from_array(def.room);
+    let pos = floor::room_origin(room) + Vec2::from(def.pos);
+    cmds.spawn((
+        Name::new("Pressure Plate"),
+        PressurePlate {
+            traps: def.traps.clone(),
+            pressed: false,
+        },
+        SpriteBundle {
+            texture: tex,
+            transform: Transform::from_translation(pos.extend(1.)),
+            ..default()
+        },
+        Collider::cuboid(6., 2.),
+        Sensor,
+        InRoom(room),
+    ))
+    .id()
+}
+
+fn spawn_room_traps(
+    mut cmds: Commands,
+    mut furnished: Local<HashSet<IVec2>>,
+    asset_server: Res<AssetServer>,
+    layout_handle: Res<TrapLayoutHandle>,
+    layouts: Res<Assets<TrapLayout>>,
+    floor_state: Res<FloorState>,
+) {
+    let Some(layout) = layouts.get(&layout_handle.0) else {
+        return;
+    };
+    furnished.retain(|room| floor_state.generated.contains(room));
+
+    for &room in floor_state.generated.iter() {
+        if !furnished.insert(room) {
+            continue;
+        }
+        let in_room = |def_room: [i32; 2]| IVec2::from_array(def_room) == room;
+
+        for trap_def in layout
+            .traps
+            .iter()
+            .filter(|trap_def| in_room(trap_def.room))
+        {
+            spawn_trap(&mut cmds, &asset_server, trap_def);
+        }
+        for plate_def in layout
+            .plates
+            .iter()
+            .filter(|plate_def| in_room(plate_def.room))
+        {
+            spawn_plate(&mut cmds, asset_server.load("plate.png"), plate_def);
+        }
+    }
+}
+
+fn actor_filter() -> QueryFilter<'static> {
+    QueryFilter::new()
+        .exclude_sensors()
+        .groups(CollisionGroups::new(Group::ALL, ACTOR_GROUP))
+}
+
+fn press_plates(
+    rapier_ctx: Res<RapierContext>,
+    mut trigger_trap_evw: EventWriter<TriggerTrap>,
+    mut plate_qry: Query<(&mut PressurePlate, &mut Sprite, &Transform, &Collider)>,
+) {
+    for (mut plate, mut sprite, plate_xform, collider) in plate_qry.iter_mut() {
+        let mut pressed = false;
+        rapier_ctx.intersections_with_shape(
+            plate_xform.translation.truncate(),
+            0.,
+            collider,
+            actor_filter(),
+            |_| {
+                pressed = true;
+                false
+            },
+        );
+        if pressed == plate.pressed {
+            continue;
+        }
+        plate.pressed = pressed;
+        sprite.color = if pressed { Color::GRAY } else { Color::WHITE };
+        if pressed {
+            for id in plate.traps.iter() {
+                trigger_trap_evw.send(TriggerTrap { id: id.clone() });
+            }
+        }
+    }
+}
+
+fn scaled_timestep(time: &Time<Fixed>, time_scale: Option<&TimeScale>) -> Duration {
+    time.timestep()
+        .mul_f32(time_scale.map_or(1., |time_scale| time_scale.0))
+}
+
+fn tick_traps(time: Res<Time<Fixed>>, mut trap_qry: Query<(&mut Trap, Option<&TimeScale>)>) {
+    for (mut trap, time_scale) in trap_qry.iter_mut() {
+        let dt = scaled_timestep(&time, time_scale).as_secs_f32();
+        trap.prev_elapsed = trap.elapsed;
+        trap.elapsed += dt;
+        for (_, cooldown) in trap.cooldowns.iter_mut() {
+            *cooldown -= dt;
+        }
+        trap.cooldowns.retain(|(_, cooldown)| *cooldown > 0.);
+    }
+}
+
+fn triggered<'a>(trap: &Trap, mut ids: impl Iterator<Item = &'a TriggerTrap>) -> bool {
+    trap.id
+        .as_ref()
+        .is_some_and(|id| ids.any(|trigger| &trigger.id == id))
+}
+
+fn cycle_spikes(
+    time: Res<Time<Fixed>>,
+    mut trigger_trap_evr: EventReader<TriggerTrap>,
+    mut spikes_qry: Query<(&mut Trap, &mut Spikes, &mut Visibility, Option<&TimeScale>)>,
+) {
+    let triggers = trigger_trap_evr.read().collect::<Vec<_>>();
+
+    for (mut trap, mut spikes, mut visibility, time_scale) in spikes_qry.iter_mut() {
+        if triggered(&trap, triggers.iter().copied()) {
+            spikes.triggered = SPIKE_TRIGGER_SECONDS;
+        }
+        let dt = scaled_timestep(&time, time_scale).as_secs_f32();
+        spikes.triggered = (spikes.triggered - dt).max(0.);
+
+        let armed = spikes.triggered > 0.
+            || spikes
+                .timing
+                .map_or(trap.id.is_none(), |timing| timing.is_active(trap.elapsed));
+        if trap.armed != armed {
+            trap.armed = armed;
+            *visibility = if armed {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+        }
+    }
+}
+
+fn swing_blades(mut blade_qry: Query<(&Trap, &Blade, &mut Transform)>) {
+    for (trap, blade, mut blade_xform) in blade_qry.iter_mut() {
+        let angle = blade.arc / 2. * (TAU * trap.elapsed / blade.period).sin();
+        blade_xform.rotation = Quat::from_rotation_z(angle);
+    }
+}
+
+fn fire_arrow_traps(
+    mut cmds: Commands,
+    asset_server: Res<AssetServer>,
+    mut trigger_trap_evr: EventReader<TriggerTrap>,
+    arrow_trap_qry: Query<(Entity, &Trap, &ArrowTrap, &Transform)>,
+) {
+    let triggers = trigger_trap_evr.read().collect::<Vec<_>>();
+
+    for (trap_id, trap, arrow_trap, trap_xform) in arrow_trap_qry.iter() {
+        let timed = arrow_trap
+            .timing
+            .is_some_and(|timing| timing.started(trap.prev_elapsed, trap.elapsed));
+        if !timed && !triggered(trap, triggers.iter().copied()) {
+            continue;
+        }
+        let arrow = ProjectileDef {
+            sprite: String::from("arrow.png"),
+            speed: ARROW_SPEED,
+            gravity: 0.,
+            lifetime: ARROW_LIFETIME,
+            pierce: 0,
+            bounces: 0,
+            light: None,
+            status: trap.status,
+        };
+        projectile::spawn_projectile(
+            &mut cmds,
+            asset_server.load(&arrow.sprite),
+            Projectile::new(&arrow, Some(trap_id), trap.damage, CONTACT_KNOCKBACK / 2.),
+            trap_xform.translation.truncate() + arrow_trap.direction * 10.,
+            arrow_trap.direction * arrow.speed,
+        );
+    }
+}
+
+fn drop_falling_blocks(
+    rapier_ctx: Res<RapierContext>,
+    mut trigger_trap_evr: EventReader<TriggerTrap>,
+    mut block_qry: Query<(
+        Entity,
+        &mut Trap,
+        &mut FallingBlock,
+        &mut NetDirection,
+        &Grounded,
+        &Transform,
+    )>,
+    actor_qry: Query<&Transform, With<Health>>,
+) {
+    let triggers = trigger_trap_evr.read().collect::<Vec<_>>();
+
+    for (block_id, mut trap, mut block, mut net_dir, grounded, block_xform) in block_qry.iter_mut()
+    {
+        if block.falling {
+            if grounded.0 {
+                block.falling = false;
+                trap.armed = false;
+            }
+            continue;
+        }
+        if net_dir.y != 0 {
+            continue;
+        }
+        let block_pos = block_xform.translation.truncate();
+        let below = trap.id.is_none()
+            && actor_qry.iter().any(|actor_xform| {
+                let delta = actor_xform.translation.truncate() - block_pos;
+                delta.x.abs() < FALL_TRIGGER_WIDTH
+                    && delta.y < 0.
+                    && delta.y > -FALL_TRIGGER_RANGE
+                    && rapier_ctx
+                        .cast_ray(
+                            block_pos,
+                            delta,
+                            1.,
+                            true,
+                            QueryFilter::new()
+                                .exclude_sensors()
+                                .exclude_collider(block_id)
+                                .groups(CollisionGroups::new(Group::ALL, TILE_GROUP)),
+                        )
+                        .is_none()
+            });
+        if below || triggered(&trap, triggers.iter().copied()) {
+            block.falling = true;
+            trap.armed = true;
+            net_dir.y = -1;
+        }
+    }
+}
+
+fn crumble_floors(
+    mut cmds: Commands,
+    time: Res<Time<Fixed>>,
+    actor_qry: Query<(&KinematicCharacterControllerOutput, &Grounded), With<Health>>,
+    mut floor_qry: Query<(
+        Entity,
+        &mut CrumblingFloor,
+        &mut Visibility,
+        Option<&TimeScale>,
+    )>,
+) {
+    for (kcc_out, grounded) in actor_qry.iter() {
+        if !grounded.0 {
+            continue;
+        }
+        for collision in kcc_out.collisions.iter() {
+            let standing = collision
+                .toi
+                .details
+                .is_some_and(|deets| deets.normal2.y > 0.8);
+            let Ok((_, mut floor, _, _)) = floor_qry.get_mut(collision.entity) else {
+                continue;
+            };
+            if standing && floor.timer.is_none() && !floor.crumbled {
+                floor.timer = Some(Timer::from_seconds(floor.delay, TimerMode::Once));
+            }
+        }
+    }
+
+    for (floor_id, mut floor, mut visibility, time_scale) in floor_qry.iter_mut() {
+        let Some(timer) = floor.timer.as_mut() else {
+            continue;
+        };
+        if !timer.tick(scaled_timestep(&time, time_scale)).finished() {
+            continue;
+        }
+        floor.crumbled = !floor.crumbled;
+        if floor.crumbled {
+            floor.timer = Some(Timer::from_seconds(floor.respawn, TimerMode::Once));
+            *visibility = Visibility::Hidden;
+            cmds.entity(floor_id).remove::<Collider>();
+        } else {
+            floor.timer = None;
+            *visibility = Visibility::Inherited;
+            cmds.entity(floor_id).insert(Collider::cuboid(8., 8.));
+        }
+    }
+}
+
+fn damage_on_contact(
+    rapier_ctx: Res<RapierContext>,
+    mut damage_evw: EventWriter<DamageEvent>,
+    mut apply_status_evw: EventWriter<ApplyStatus>,
+    mut trap_qry: Query<(Entity, &mut Trap, &Collider, &Transform)>,
+    health_qry: Query<&Transform, With<Health>>,
+) {
+    for (trap_id, mut trap, collider, trap_xform) in trap_qry.iter_mut() {
+        if !trap.armed || (trap.damage == 0 && trap.status.is_none()) {
+            continue;
+        }
+        let trap_pos = trap_xform.translation.truncate();
+        let mut hits = Vec::new();
+
+        rapier_ctx.intersections_with_shape(
+            trap_pos,
+            trap_xform.rotation.to_euler(EulerRot::ZYX).0,
+            collider,
+            actor_filter().exclude_collider(trap_id),
+            |target_id| {
+                if !trap.cooldowns.iter().any(|&(id, _)| id == target_id) {
+                    hits.push(target_id);
+                }
+                true
+            },
+        );
+        for target_id in hits {
+            let Ok(target_xform) = health_qry.get(target_id) else {
+                continue;
+            };
+            let away = (target_xform.translation.x - trap_pos.x).signum();
+            trap.cooldowns.push((target_id, CONTACT_COOLDOWN));
+            damage_evw.send(DamageEvent {
+                target: target_id,
+                amount: trap.damage,
+                knockback: Vec2::new(away, 1.).normalize() * CONTACT_KNOCKBACK,
+            });
+            if let Some(effect) = trap.status {
+                apply_status_evw.send(ApplyStatus {
+                    target: target_id,
+                    effect,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            player::Player,
+            simulation::Simulation,
+            status_effect::{StatusEffects, StatusKind},
+            tile::{self, TileWorld},
+        },
+        bevy::ecs::system::RunSystemOnce,
+        bevy_ecs_tilemap::prelude::*,
+        std::fs,
+    };
+
+    fn spawn(sim: &mut Simulation, def: TrapDef) -> Entity {
+        sim.app
+            .world
+            .run_system_once(move |mut cmds: Commands, asset_server: Res<AssetServer>| {
+                spawn_trap(&mut cmds, &asset_server, &def)
+            })
+    }
+
+    #[test]
+    fn timing_windows_repeat() {
+        let timing = Timing {
+            period: 2.,
+            active: 0.5,
+            offset: 0.25,
+        };
+        assert!(!timing.is_active(0.));
+        assert!(timing.is_active(1.8));
+        assert!(!timing.is_active(2.5));
+        assert!(timing.started(1.7, 1.8));
+        assert!(!timing.started(1.8, 1.9));
+    }
+
+    #[test]
+    fn trap_layout_parses() {
+        let layout = ron::de::from_str::<TrapLayout>(
+            &fs::read_to_string("assets/data/base.traps.ron").unwrap(),
+        )
+        .unwrap();
+        assert!(layout
+            .plates
+            .iter()
+            .flat_map(|plate_def| plate_def.traps.iter())
+            .all(|id| layout
+                .traps
+                .iter()
+                .any(|trap_def| trap_def.id.as_ref() == Some(id))));
+    }
+
+    #[test]
+    fn traps_respawn_with_their_rooms() {
+        let mut sim = Simulation::new();
+        let layout = ron::de::from_str::<TrapLayout>(
+            &fs::read_to_string("assets/data/base.traps.ron").unwrap(),
+        )
+        .unwrap();
+        let trap_count = layout.traps.len();
+        let layout_handle = sim
+            .app
+            .world
+            .resource_mut::<Assets<TrapLayout>>()
+            .add(layout);
+        sim.app
+            .world
+            .insert_resource(TrapLayoutHandle(layout_handle));
+        sim.step_n(2, &[]);
+        let traps = |sim: &mut Simulation| {
+            sim.app
+                .world
+                .query_filtered::<&InRoom, With<Trap>>()
+                .iter(&sim.app.world)
+                .count()
+        };
+        assert_eq!(traps(&mut sim), trap_count);
+
+        let mut floor_state = sim.app.world.resource_mut::<FloorState>();
+        floor_state.generated.clear();
+        floor_state.populated.clear();
+        sim.step_n(3, &[]);
+        assert_eq!(traps(&mut sim), trap_count);
+    }
+
+    #[test]
+    fn spikes_damage_once_per_cooldown() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let start = sim.player::<Transform>().translation.truncate();
+        spawn(
+            &mut sim,
+            TrapDef {
+                id: None,
+                room: [0, 0],
+                pos: [start.x, start.y - 6.],
+                kind: TrapKind::Spikes { timing: None },
+                damage: 1,
+                status: None,
+            },
+        );
+
+        sim.step_n(8, &[]);
+        assert_eq!(sim.player::<Health>().current, 4);
+
+        let pin = |sim: &mut Simulation| {
+            let (mut player_xform, mut player_vel) = sim
+                .app
+                .world
+                .query_filtered::<(&mut Transform, &mut Velocity), With<Player>>()
+                .single_mut(&mut sim.app.world);
+            player_xform.translation = start.extend(player_xform.translation.z);
+            player_vel.linvel = Vec2::ZERO;
+            sim.step(&[]);
+        };
+        let cooldown_ticks = (CONTACT_COOLDOWN / physics::TIMESTEP.as_secs_f32()) as usize;
+        for _ in 8..cooldown_ticks {
+            pin(&mut sim);
+        }
+        assert_eq!(sim.player::<Health>().current, 4);
+
+        for _ in 0..16 {
+            pin(&mut sim);
+        }
+        assert_eq!(sim.player::<Health>().current, 3);
+    }
+
+    #[test]
+    fn poisoned_darts_afflict_the_player() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let start = sim.player::<Transform>().translation.truncate();
+        spawn(
+            &mut sim,
+            TrapDef {
+                id: Some(String::from("dart")),
+                room: [0, 0],
+                pos: [start.x + 48., start.y],
+                kind: TrapKind::ArrowTrap {
+                    direction: [-1., 0.],
+                    timing: None,
+                },
+                damage: 1,
+                status: Some(StatusEffect {
+                    kind: StatusKind::Poison,
+                    duration: 3.,
+                    stacks: 1,
+                }),
+            },
+        );
+        sim.step(&[]);
+        sim.app.world.send_event(TriggerTrap {
+            id: String::from("dart"),
+        });
+
+        sim.step_n(32, &[]);
+        assert_eq!(sim.player::<StatusEffects>().stacks(StatusKind::Poison), 1);
+        sim.step_n(64, &[]);
+        assert!(sim.player::<Health>().current <= 3);
+    }
+
+    #[test]
+    fn falling_blocks_need_a_clear_view_in_range() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let start = sim.player::<Transform>().translation.truncate();
+        let block = |sim: &mut Simulation, height: f32| {
+            spawn(
+                sim,
+                TrapDef {
+                    id: None,
+                    room: [0, 0],
+                    pos: [start.x, start.y + height],
+                    kind: TrapKind::FallingBlock,
+                    damage: 0,
+                    status: None,
+                },
+            )
+        };
+        let falling = |sim: &Simulation, block_id| {
+            sim.app.world.get::<FallingBlock>(block_id).unwrap().falling
+        };
+        let far_id = block(&mut sim, FALL_TRIGGER_RANGE + 32.);
+
+        let wall = tile::tile_at(start + Vec2::new(0., 48.));
+        sim.app
+            .world
+            .resource_mut::<TileWorld>()
+            .set(wall, TileTextureIndex::default());
+        sim.step_n(2, &[]);
+        let near_id = block(&mut sim, 80.);
+        sim.step_n(4, &[]);
+        assert!(!falling(&sim, far_id));
+        assert!(!falling(&sim, near_id));
+
+        sim.app.world.resource_mut::<TileWorld>().remove(wall);
+        sim.step_n(4, &[]);
+        assert!(!falling(&sim, far_id));
+        assert!(falling(&sim, near_id));
+    }
+
+    #[test]
+    fn trap_timers_follow_time_scale() {
+        let mut sim = Simulation::new();
+        let def = TrapDef {
+            id: None,
+            room: [0, 0],
+            pos: [-200., -200.],
+            kind: TrapKind::Spikes { timing: None },
+            damage: 0,
+            status: None,
+        };
+        let normal_id = spawn(&mut sim, def.clone());
+        let slowed_id = spawn(&mut sim, def);
+        sim.app.world.entity_mut(slowed_id).insert(TimeScale(0.5));
+
+        sim.step_n(16, &[]);
+        let elapsed =
+            |sim: &Simulation, trap_id| sim.app.world.get::<Trap>(trap_id).unwrap().elapsed;
+        assert!(elapsed(&sim, normal_id) > 0.);
+        assert!((elapsed(&sim, slowed_id) * 2. - elapsed(&sim, normal_id)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn crumbling_floor_gives_way_under_the_player() {
+        let mut sim = Simulation::new();
+        sim.settle(256);
+        let start = sim.player::<Transform>().translation.truncate();
+        spawn(
+            &mut sim,
+            TrapDef {
+                id: None,
+                room: [0, 0],
+                pos: [start.x, start.y + 40.],
+                kind: TrapKind::CrumblingFloor {
+                    delay: 0.25,
+                    respawn: 5.,
+                },
+                damage: 0,
+                status: None,
+            },
+        );
+        sim.app
+            .world
+            .query_filtered::<&mut Transform, With<Player>>()
+            .single_mut(&mut sim.app.world)
+            .translation
+            .y = start.y + 64.;
+
+        sim.step_n(12, &[]);
+        assert!(sim.player::<Transform>().translation.y > start.y + 40.);
+
+        sim.step_n(64, &[]);
+        assert!((sim.player::<Transform>().translation.y - start.y).abs() < 1.);
+    }
+}
@@ -1,23 +1,328 @@
-use {super::game_state::GameState, bevy::prelude::*, std::f32::consts::PI, std::time::Duration};
+use {
+    super::{
+        aim::AimTarget,
+        game_state::GameState,
+        health::{DamageEvent, Health},
+        physics::ACTOR_GROUP,
+        player::{self, PlayerAction, TickActions},
+        projectile::ProjectileDef,
+        ron_asset::RonAssetPlugin,
+        sprite_flip::Flippable,
+        stats::Stats,
+        status_effect::StatusEffects,
+        time_control::{TimeControl, TimeScale},
+    },
+    bevy::prelude::*,
+    bevy_rapier2d::prelude::*,
+    serde::Deserialize,
+    std::f32::consts::{FRAC_PI_2, PI},
+};
 
 pub struct WeaponPlugin;
 
 impl Plugin for WeaponPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, swing_weapons.run_if(in_state(GameState::Playing)));
+        app.add_plugins(RonAssetPlugin::<WeaponDef>::new(&["weapon.ron"]))
+            .add_event::<WeaponFired>()
+            .add_systems(
+                Update,
+                attach_weapon_sprites.run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (start_attacks, swing_weapons, hit_with_weapons)
+                    .chain()
+                    .after(player::player_movement)
+                    .distributive_run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub enum WeaponKind {
+    Swing,
+    Thrust { distance: f32 },
+    Shoot(ProjectileDef),
+}
+
+#[derive(Asset, TypePath, Deserialize, Clone, Debug)]
+pub struct WeaponDef {
+    pub sprite: String,
+    pub kind: WeaponKind,
+    pub damage: u32,
+    pub reach: f32,
+    #[serde(default)]
+    pub swing_arc: f32,
+    pub swing_duration: f32,
+    pub knockback: f32,
+    pub offset: [f32; 2],
+    #[serde(default)]
+    pub pivot: [f32; 2],
+    #[serde(default)]
+    pub hitstop: f32,
+}
+
+impl WeaponDef {
+    pub fn rest(&self, facing: f32) -> Transform {
+        Transform::from_xyz(self.offset[0] * facing, self.offset[1], -1.)
+    }
+
+    pub fn pose(&self, progress: f32, facing: f32) -> Transform {
+        let mut xform = self.rest(facing);
+        match self.kind {
+            WeaponKind::Swing => {
+                let pivot = Vec3::new(self.pivot[0] * facing, self.pivot[1], 0.);
+                let angle = -facing * self.swing_arc.to_radians() * progress;
+                xform.rotate_around(pivot, Quat::from_rotation_z(angle));
+            }
+            WeaponKind::Thrust { distance } => {
+                xform.rotation = Quat::from_rotation_z(-facing * FRAC_PI_2);
+                xform.translation.x += facing * distance * (progress * PI).sin();
+            }
+            WeaponKind::Shoot(_) => {}
+        }
+        xform
     }
 }
 
 #[derive(Component)]
-pub struct Weapon;
+pub struct Weapon {
+    pub def: Handle<WeaponDef>,
+    swing: Option<Timer>,
+    hits: Vec<Entity>,
+}
+
+impl Weapon {
+    pub fn new(def: Handle<WeaponDef>) -> Self {
+        Self {
+            def,
+            swing: None,
+            hits: Vec::new(),
+        }
+    }
+
+    pub fn cooldown(&self) -> f32 {
+        self.swing.as_ref().map_or(0., |swing| swing.percent_left())
+    }
+}
+
+#[derive(Event)]
+pub struct WeaponFired {
+    pub owner: Entity,
+    pub origin: Vec2,
+    pub direction: Vec2,
+    pub damage: u32,
+    pub knockback: f32,
+    pub projectile: ProjectileDef,
+}
+
+fn facing(flippable: &Flippable) -> f32 {
+    if flippable.flip_x {
+        -1.
+    } else {
+        1.
+    }
+}
+
+fn attach_weapon_sprites(
+    weapon_defs: Res<Assets<WeaponDef>>,
+    asset_server: Res<AssetServer>,
+    mut weapon_qry: Query<(&Weapon, &Flippable, &mut Transform, &mut Handle<Image>)>,
+) {
+    for (weapon, weapon_flippable, mut weapon_xform, mut tex) in weapon_qry.iter_mut() {
+        if *tex != Handle::default() {
+            continue;
+        }
+        if let Some(weapon_def) = weapon_defs.get(&weapon.def) {
+            *tex = asset_server.load(&weapon_def.sprite);
+            *weapon_xform = weapon_def.rest(facing(weapon_flippable));
+        }
+    }
+}
+
+fn start_attacks(
+    weapon_defs: Res<Assets<WeaponDef>>,
+    mut weapon_fired_evw: EventWriter<WeaponFired>,
+    mut weapon_qry: Query<(&mut Weapon, &Parent, &GlobalTransform)>,
+    owner_qry: Query<(
+        &TickActions,
+        Option<&AimTarget>,
+        Option<&Stats>,
+        Option<&StatusEffects>,
+    )>,
+) {
+    for (mut weapon, parent, weapon_glob_xform) in weapon_qry.iter_mut() {
+        if weapon.swing.is_some() {
+            continue;
+        }
+        let Ok((owner_tick_actions, owner_aim_target, owner_stats, owner_status_effects)) =
+            owner_qry.get(parent.get())
+        else {
+            continue;
+        };
+        if !owner_tick_actions.pressed(PlayerAction::Attack)
+            || owner_status_effects.is_some_and(|status_effects| status_effects.stunned())
+        {
+            continue;
+        }
+        let Some(weapon_def) = weapon_defs.get(&weapon.def) else {
+            continue;
+        };
+
+        weapon.swing = Some(Timer::from_seconds(
+            weapon_def.swing_duration,
+            TimerMode::Once,
+        ));
+        weapon.hits.clear();
+
+        if let WeaponKind::Shoot(projectile) = &weapon_def.kind {
+            let origin = weapon_glob_xform.translation().truncate();
+            let direction = owner_aim_target
+                .map(|aim_target| (**aim_target - origin).normalize_or_zero())
+                .unwrap_or(Vec2::X);
+            weapon_fired_evw.send(WeaponFired {
+                owner: parent.get(),
+                origin,
+                direction,
+                damage: owner_stats
+                    .map_or(weapon_def.damage, |stats| stats.damage(weapon_def.damage)),
+                knockback: weapon_def.knockback,
+                projectile: projectile.clone(),
+            });
+        }
+    }
+}
+
+fn swing_weapons(
+    time: Res<Time<Fixed>>,
+    weapon_defs: Res<Assets<WeaponDef>>,
+    mut weapon_qry: Query<(&mut Weapon, &Parent, &Flippable, &mut Transform)>,
+    owner_qry: Query<&TimeScale>,
+) {
+    for (mut weapon, parent, weapon_flippable, mut weapon_xform) in weapon_qry.iter_mut() {
+        let Some(weapon_def) = weapon_defs.get(&weapon.def) else {
+            continue;
+        };
+        let Some(swing) = weapon.swing.as_mut() else {
+            continue;
+        };
+
+        let scale = owner_qry
+            .get(parent.get())
+            .map_or(1., |time_scale| time_scale.0);
+        swing.tick(time.timestep().mul_f32(scale));
+        let facing = facing(weapon_flippable);
+        if swing.finished() {
+            weapon.swing = None;
+            *weapon_xform = weapon_def.rest(facing);
+        } else {
+            *weapon_xform = weapon_def.pose(swing.percent(), facing);
+        }
+    }
+}
+
+fn hit_with_weapons(
+    rapier_ctx: Res<RapierContext>,
+    weapon_defs: Res<Assets<WeaponDef>>,
+    mut damage_evw: EventWriter<DamageEvent>,
+    mut time_control: ResMut<TimeControl>,
+    mut weapon_qry: Query<(&mut Weapon, &Parent, &GlobalTransform)>,
+    owner_qry: Query<&Stats>,
+    health_qry: Query<(), With<Health>>,
+) {
+    for (mut weapon, parent, weapon_glob_xform) in weapon_qry.iter_mut() {
+        if weapon.swing.is_none() {
+            continue;
+        }
+        let Some(weapon_def) = weapon_defs.get(&weapon.def) else {
+            continue;
+        };
+        if matches!(weapon_def.kind, WeaponKind::Shoot(_)) {
+            continue;
+        }
+
+        let grip = weapon_glob_xform.translation().truncate();
+        let tip = weapon_glob_xform
+            .transform_point(Vec3::Y * weapon_def.reach)
+            .truncate();
+        let knockback = (tip - grip).normalize_or_zero() * weapon_def.knockback;
+        let damage = owner_qry
+            .get(parent.get())
+            .map_or(weapon_def.damage, |stats| stats.damage(weapon_def.damage));
+        let mut hits = Vec::new();
+
+        rapier_ctx.intersections_with_shape(
+            Vec2::ZERO,
+            0.,
+            &Collider::capsule(grip, tip, 2.),
+            QueryFilter::new()
+                .exclude_sensors()
+                .exclude_collider(parent.get())
+                .groups(CollisionGroups::new(Group::ALL, ACTOR_GROUP)),
+            |target_id| {
+                if health_qry.contains(target_id) && !weapon.hits.contains(&target_id) {
+                    hits.push(target_id);
+                }
+                true
+            },
+        );
+        if !hits.is_empty() {
+            time_control.hitstop(weapon_def.hitstop);
+        }
+        for target_id in hits {
+            weapon.hits.push(target_id);
+            damage_evw.send(DamageEvent {
+                target: target_id,
+                amount: damage,
+                knockback,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::fs};
+
+    #[test]
+    fn weapon_files_parse() {
+        for entry in fs::read_dir("assets/weapons").unwrap() {
+            let path = entry.unwrap().path();
+            let weapon_def = ron::de::from_str::<WeaponDef>(&fs::read_to_string(&path).unwrap());
+            assert!(
+                weapon_def.is_ok(),
+                "{}: {:?}",
+                path.display(),
+                weapon_def.err()
+            );
+        }
+    }
+
+    #[test]
+    fn poses_mirror_with_facing() {
+        let weapon_def = |kind| WeaponDef {
+            sprite: String::new(),
+            kind,
+            damage: 1,
+            reach: 14.,
+            swing_arc: 90.,
+            swing_duration: 0.25,
+            knockback: 0.,
+            offset: [2., 5.],
+            pivot: [-6., 6.],
+            hitstop: 0.,
+        };
 
-fn swing_weapons(time: Res<Time>, mut weapon_qry: Query<&mut Transform, With<Weapon>>) {
-    let dt = time.delta_seconds();
+        let sword = weapon_def(WeaponKind::Swing);
+        assert_eq!(sword.pose(0., 1.), sword.rest(1.));
+        let right = sword.pose(1., 1.);
+        let left = sword.pose(1., -1.);
+        assert!((right.translation.x + left.translation.x).abs() < 1e-4);
+        assert!((right.translation.y - left.translation.y).abs() < 1e-4);
+        assert!(right.rotation.to_euler(EulerRot::XYZ).2 < 0.);
 
-    for mut weapon_xform in weapon_qry.iter_mut() {
-        let rotation_dir =
-            -(weapon_xform.translation.x.signum() * weapon_xform.translation.y.signum());
-        //weapon_xform.rotation.z = weapon_xform.rotation.z.clamp(PI / 8., 2. * PI) * rotation_dir;
-        weapon_xform.rotate_around(Vec3::new(-6., 6., 0.), Quat::from_rotation_z(5. * dt));
+        let spear = weapon_def(WeaponKind::Thrust { distance: 8. });
+        assert!((spear.pose(0.5, 1.).translation.x - 10.).abs() < 1e-4);
+        assert!((spear.pose(0.5, -1.).translation.x + 10.).abs() < 1e-4);
     }
 }